@@ -0,0 +1,82 @@
+use std::ops::{Index, IndexMut};
+
+use crate::{
+    diagnostic::Diagnostic, expression::Expr, statement::Stmt, symbol::Symbol, token::Token,
+};
+
+/// An expression in an `Ast`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(pub(crate) u32);
+
+/// A statement in an `Ast`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StmtId(pub(crate) u32);
+
+/// Every node of a program. Nodes refer to their children by id, so a program is two vectors
+/// instead of a tree of boxes, and function values share the program's `Ast` through an `Rc`
+/// instead of copying their body.
+#[derive(Debug, Default)]
+pub struct Ast {
+    expressions: Vec<Expr>,
+    statements: Vec<Stmt>,
+}
+
+impl Ast {
+    pub fn add_expression(&mut self, expression: Expr) -> ExprId {
+        self.expressions.push(expression);
+        ExprId(self.expressions.len() as u32 - 1)
+    }
+
+    pub fn add_statement(&mut self, statement: Stmt) -> StmtId {
+        self.statements.push(statement);
+        StmtId(self.statements.len() as u32 - 1)
+    }
+
+    pub(crate) fn expressions(&self) -> &[Expr] {
+        &self.expressions
+    }
+
+    pub(crate) fn statements(&self) -> &[Stmt] {
+        &self.statements
+    }
+}
+
+/// A parsed and resolved program, ready for either engine.
+#[derive(Debug, Default)]
+pub struct Program {
+    pub ast: Ast,
+    pub statements: Vec<StmtId>,
+    /// Globals that a local in scope is a typo away from, with those locals, for suggestions
+    /// when the global turns out to be undefined.
+    pub suggestions: Vec<(Token, Vec<Symbol>)>,
+    /// The parser's warnings, kept so a program from the cache reports them again.
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl Index<ExprId> for Ast {
+    type Output = Expr;
+
+    fn index(&self, id: ExprId) -> &Expr {
+        &self.expressions[id.0 as usize]
+    }
+}
+
+impl IndexMut<ExprId> for Ast {
+    fn index_mut(&mut self, id: ExprId) -> &mut Expr {
+        &mut self.expressions[id.0 as usize]
+    }
+}
+
+impl Index<StmtId> for Ast {
+    type Output = Stmt;
+
+    fn index(&self, id: StmtId) -> &Stmt {
+        &self.statements[id.0 as usize]
+    }
+}
+
+impl IndexMut<StmtId> for Ast {
+    fn index_mut(&mut self, id: StmtId) -> &mut Stmt {
+        &mut self.statements[id.0 as usize]
+    }
+}
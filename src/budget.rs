@@ -0,0 +1,80 @@
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{memory, IntError};
+
+// reading the clock on every step would cost more than the step itself
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// The limit that stopped a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Steps(u64),
+    Time(Duration),
+    Interrupt,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Steps(steps) => write!(f, "step limit of {steps} exceeded"),
+            Limit::Time(time) => write!(f, "time limit of {}s exceeded", time.as_secs_f64()),
+            Limit::Interrupt => f.write_str("interrupted"),
+        }
+    }
+}
+
+/// How long a program may run. A step is a statement in the tree walker and an instruction in the
+/// vm, the limits apply to every `interpret` separately.
+#[derive(Debug, Default)]
+pub struct Budget {
+    pub max_steps: Option<u64>,
+    pub time_limit: Option<Duration>,
+    interrupt: Arc<AtomicBool>,
+    steps: u64,
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    /// The flag that stops the running program when it's set, from any thread.
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool> {
+        self.interrupt.clone()
+    }
+
+    /// Starts counting for a new program. An interrupt that came while nothing was running is
+    /// dropped.
+    pub fn start(&mut self) {
+        self.steps = 0;
+        self.deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        self.interrupt.store(false, Ordering::Relaxed);
+    }
+
+    pub fn step(&mut self) -> Result<(), IntError> {
+        self.steps += 1;
+        if let Some(max_steps) = self.max_steps {
+            if self.steps > max_steps {
+                return Err(IntError::Stopped(Limit::Steps(max_steps)));
+            }
+        }
+        // an interrupt stops the program once, a `try` that catches it carries on
+        if self.interrupt.swap(false, Ordering::Relaxed) {
+            return Err(IntError::Stopped(Limit::Interrupt));
+        }
+        if self.steps.is_multiple_of(CLOCK_INTERVAL) {
+            if let (Some(deadline), Some(limit)) = (self.deadline, self.time_limit) {
+                if Instant::now() >= deadline {
+                    return Err(IntError::Stopped(Limit::Time(limit)));
+                }
+            }
+        }
+        // big allocations are checked before they're made, this catches the small ones adding up
+        memory::check()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,925 @@
+use ahash::AHashSet as HashSet;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast::{Ast, ExprId, Program, StmtId},
+    diagnostic::{Code, Diagnostic},
+    environment::Slot,
+    expression::Expr,
+    resolver::Resolver,
+    statement::Stmt,
+    symbol::Symbol,
+    token::{Token, TokenKind},
+    value::Value,
+};
+
+const MAGIC: &[u8; 4] = b"INTC";
+/// Changes whenever the encoding or the ast changes, caches written by another version are
+/// ignored.
+pub const FORMAT_VERSION: u32 = 4;
+/// The extension of cache files.
+pub const EXTENSION: &str = "intc";
+
+// the codes of the warnings the parser gives, encoded by their position like the token kinds
+const WARNING_CODES: [Code; 1] = [Code::InvalidEscape];
+
+// the operators the engines evaluate
+const UNARY_OPERATORS: [TokenKind; 2] = [TokenKind::Minus, TokenKind::Bang];
+const BINARY_OPERATORS: [TokenKind; 13] = [
+    TokenKind::Minus,
+    TokenKind::Slash,
+    TokenKind::Star,
+    TokenKind::Plus,
+    TokenKind::BangEqual,
+    TokenKind::EqualEqual,
+    TokenKind::Is,
+    TokenKind::IsNot,
+    TokenKind::Greater,
+    TokenKind::GreaterEqual,
+    TokenKind::Less,
+    TokenKind::LessEqual,
+    TokenKind::Comma,
+];
+const LOGICAL_OPERATORS: [TokenKind; 2] = [TokenKind::And, TokenKind::Or];
+
+// the position of a kind in this list is its encoding, new kinds go at the end
+const TOKEN_KINDS: [TokenKind; 52] = [
+    TokenKind::LeftParen,
+    TokenKind::RightParen,
+    TokenKind::LeftBrace,
+    TokenKind::RightBrace,
+    TokenKind::LeftBracket,
+    TokenKind::RightBracket,
+    TokenKind::Comma,
+    TokenKind::Dot,
+    TokenKind::Minus,
+    TokenKind::Plus,
+    TokenKind::Semicolon,
+    TokenKind::Slash,
+    TokenKind::Star,
+    TokenKind::Question,
+    TokenKind::Colon,
+    TokenKind::Bang,
+    TokenKind::BangEqual,
+    TokenKind::Equal,
+    TokenKind::EqualEqual,
+    TokenKind::Greater,
+    TokenKind::GreaterEqual,
+    TokenKind::Less,
+    TokenKind::LessEqual,
+    TokenKind::IsNot,
+    TokenKind::Identifier,
+    TokenKind::String,
+    TokenKind::Number,
+    TokenKind::And,
+    TokenKind::Class,
+    TokenKind::Else,
+    TokenKind::False,
+    TokenKind::Fun,
+    TokenKind::For,
+    TokenKind::If,
+    TokenKind::Is,
+    TokenKind::Nil,
+    TokenKind::Not,
+    TokenKind::Or,
+    TokenKind::Print,
+    TokenKind::Return,
+    TokenKind::Break,
+    TokenKind::Continue,
+    TokenKind::Super,
+    TokenKind::This,
+    TokenKind::True,
+    TokenKind::Var,
+    TokenKind::While,
+    TokenKind::Append,
+    TokenKind::Insert,
+    TokenKind::Delete,
+    TokenKind::Eof,
+    TokenKind::Error,
+];
+
+/// A hash of the source a cache was made from, FNV-1a so it's the same on every build.
+pub fn hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Where caches go when no directory is given: `$XDG_CACHE_HOME/int`, or `~/.cache/int` when
+/// that isn't set. None without either variable.
+pub fn default_dir() -> Option<PathBuf> {
+    let absolute = |name| {
+        std::env::var_os(name)
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+    };
+    absolute("XDG_CACHE_HOME")
+        .or_else(|| absolute("HOME").map(|home| home.join(".cache")))
+        .map(|cache| cache.join("int"))
+}
+
+/// Writes a cache to `path`, making its directory if needed.
+pub fn write(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    fs::write(path, bytes)
+}
+
+/// Where the cache of `script` lives in `cache_dir`, under a name made from the script's path.
+pub fn path(script: &Path, cache_dir: &Path) -> PathBuf {
+    let script = script
+        .canonicalize()
+        .unwrap_or_else(|_| script.to_path_buf());
+    let stem = script.file_stem().unwrap_or_default().to_string_lossy();
+    let key = hash(&script.to_string_lossy());
+    cache_dir.join(format!("{stem}-{key:016x}.{EXTENSION}"))
+}
+
+/// Serializes a program parsed from `source`. `offset` is where `source` starts in the
+/// interpreter's source, the spans of the cache are relative to `source`.
+pub fn encode(program: &Program, source: &str, offset: usize) -> Vec<u8> {
+    let mut encoder = Encoder {
+        bytes: Vec::new(),
+        offset,
+    };
+    encoder.bytes.extend_from_slice(MAGIC);
+    encoder.u32(FORMAT_VERSION);
+    encoder.u64(hash(source));
+    let ast = &program.ast;
+    encoder.u32(ast.expressions().len() as u32);
+    encoder.u32(ast.statements().len() as u32);
+    for expression in ast.expressions() {
+        encoder.expression(expression);
+    }
+    for statement in ast.statements() {
+        encoder.statement(statement);
+    }
+    encoder.statements(&program.statements);
+    encoder.u32(program.warnings.len() as u32);
+    for warning in &program.warnings {
+        encoder.warning(warning);
+    }
+    encoder.bytes
+}
+
+/// The program in `bytes` if it was made from `source` by this version, with its spans moved to
+/// start at `offset`. The slots aren't stored, the program is resolved again.
+pub fn decode(bytes: &[u8], source: &str, offset: usize) -> Option<Program> {
+    let mut decoder = Decoder {
+        bytes,
+        source,
+        offset,
+        expressions: 0,
+        statements: 0,
+    };
+    if decoder.take(MAGIC.len())? != MAGIC
+        || decoder.u32()? != FORMAT_VERSION
+        || decoder.u64()? != hash(source)
+    {
+        return None;
+    }
+    decoder.expressions = decoder.u32()?;
+    decoder.statements = decoder.u32()?;
+    let mut ast = Ast::default();
+    for _ in 0..decoder.expressions {
+        ast.add_expression(decoder.expression()?);
+    }
+    for _ in 0..decoder.statements {
+        ast.add_statement(decoder.statement()?);
+    }
+    let statements = decoder.statements_list()?;
+    let warnings = (0..decoder.u32()?)
+        .map(|_| decoder.warning())
+        .collect::<Option<_>>()?;
+    if !decoder.bytes.is_empty() || !is_tree(&ast, &statements) {
+        return None;
+    }
+    let mut program = Program {
+        ast,
+        statements,
+        warnings,
+        ..Program::default()
+    };
+    // the source resolved before it was cached, errors mean the cache was damaged
+    Resolver::default()
+        .resolve(&mut program)
+        .is_empty()
+        .then_some(program)
+}
+
+struct Encoder {
+    bytes: Vec<u8>,
+    offset: usize,
+}
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn token(&mut self, token: &Token) {
+        let kind = TOKEN_KINDS
+            .iter()
+            .position(|kind| *kind == token.kind)
+            .expect("every kind is in the list");
+        self.u8(kind as u8);
+        self.u32((token.span.start - self.offset) as u32);
+        self.u32((token.span.end - self.offset) as u32);
+        self.u32(token.line as u32);
+    }
+
+    // only what the parser's warnings have, they have no labels or notes
+    fn warning(&mut self, warning: &Diagnostic) {
+        let code = WARNING_CODES
+            .iter()
+            .position(|code| *code == warning.code)
+            .expect("the parser only gives these warnings");
+        self.u8(code as u8);
+        self.string(&warning.message);
+        match warning.span {
+            Some(span) => {
+                self.u8(1);
+                self.u32((span.start - self.offset) as u32);
+                self.u32((span.end - self.offset) as u32);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn string(&mut self, string: &str) {
+        self.u32(string.len() as u32);
+        self.bytes.extend_from_slice(string.as_bytes());
+    }
+
+    fn tokens(&mut self, tokens: &[Token]) {
+        self.u32(tokens.len() as u32);
+        for token in tokens {
+            self.token(token);
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Nil => self.u8(0),
+            Value::Bool(value) => {
+                self.u8(1);
+                self.bool(*value);
+            }
+            Value::Double(value) => {
+                self.u8(2);
+                self.u64(value.to_bits());
+            }
+            value => {
+                let string = value
+                    .get_string()
+                    .expect("literals are nil, booleans, numbers or strings");
+                self.u8(3);
+                self.string(string);
+            }
+        }
+    }
+
+    fn expression_id(&mut self, id: ExprId) {
+        self.u32(id.0);
+    }
+
+    fn statement_id(&mut self, id: StmtId) {
+        self.u32(id.0);
+    }
+
+    fn expressions(&mut self, ids: &[ExprId]) {
+        self.u32(ids.len() as u32);
+        for &id in ids {
+            self.expression_id(id);
+        }
+    }
+
+    fn statements(&mut self, ids: &[StmtId]) {
+        self.u32(ids.len() as u32);
+        for &id in ids {
+            self.statement_id(id);
+        }
+    }
+
+    fn expression(&mut self, expression: &Expr) {
+        match expression {
+            Expr::Unary { operator, right } => {
+                self.u8(0);
+                self.token(operator);
+                self.expression_id(*right);
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.u8(1);
+                self.expression_id(*left);
+                self.token(operator);
+                self.expression_id(*right);
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                self.u8(2);
+                self.expression_id(*callee);
+                self.token(paren);
+                self.expressions(arguments);
+            }
+            Expr::Grouping { expression } => {
+                self.u8(3);
+                self.expression_id(*expression);
+            }
+            Expr::Literal { value } => {
+                self.u8(4);
+                self.value(value);
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.u8(5);
+                self.expression_id(*condition);
+                self.expression_id(*then_branch);
+                self.expression_id(*else_branch);
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                self.u8(6);
+                self.expression_id(*left);
+                self.token(operator);
+                self.expression_id(*right);
+            }
+            Expr::Variable { name, .. } => {
+                self.u8(7);
+                self.token(name);
+            }
+            Expr::Assign {
+                name, expression, ..
+            } => {
+                self.u8(8);
+                self.token(name);
+                self.expression_id(*expression);
+            }
+            Expr::Struct { fields } => {
+                self.u8(9);
+                self.u32(fields.len() as u32);
+                for (name, value) in fields {
+                    self.token(name);
+                    self.expression_id(*value);
+                }
+            }
+            Expr::Array { elements } => {
+                self.u8(10);
+                self.expressions(elements);
+            }
+            Expr::IndexGet {
+                array,
+                bracket,
+                index,
+            } => {
+                self.u8(11);
+                self.expression_id(*array);
+                self.token(bracket);
+                self.expression_id(*index);
+            }
+            Expr::IndexSet {
+                array,
+                bracket,
+                index,
+                value,
+            } => {
+                self.u8(12);
+                self.expression_id(*array);
+                self.token(bracket);
+                self.expression_id(*index);
+                self.expression_id(*value);
+            }
+            Expr::StructGet { target, name } => {
+                self.u8(13);
+                self.expression_id(*target);
+                self.token(name);
+            }
+            Expr::StructSet {
+                target,
+                name,
+                value,
+            } => {
+                self.u8(14);
+                self.expression_id(*target);
+                self.token(name);
+                self.expression_id(*value);
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Block { statements } => {
+                self.u8(0);
+                self.statements(statements);
+            }
+            Stmt::Expression { expression } => {
+                self.u8(1);
+                self.expression_id(*expression);
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                self.u8(2);
+                self.statements(initializer.as_slice());
+                self.expression_id(*condition);
+                self.expressions(increment.as_slice());
+                self.statement_id(*body);
+            }
+            Stmt::Function {
+                name, params, body, ..
+            } => {
+                self.u8(3);
+                self.token(name);
+                self.tokens(params);
+                self.statements(body);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.u8(4);
+                self.expression_id(*condition);
+                self.statement_id(*then_branch);
+                self.statements(else_branch.as_slice());
+            }
+            Stmt::Print { expression } => {
+                self.u8(5);
+                self.expression_id(*expression);
+            }
+            Stmt::Return { keyword, value } => {
+                self.u8(6);
+                self.token(keyword);
+                self.expression_id(*value);
+            }
+            Stmt::Break { keyword } => {
+                self.u8(7);
+                self.token(keyword);
+            }
+            Stmt::Continue { keyword } => {
+                self.u8(8);
+                self.token(keyword);
+            }
+            Stmt::Var {
+                initializer, name, ..
+            } => {
+                self.u8(9);
+                self.expression_id(*initializer);
+                self.token(name);
+            }
+            Stmt::While { condition, body } => {
+                self.u8(10);
+                self.expression_id(*condition);
+                self.statement_id(*body);
+            }
+            Stmt::Append {
+                paren,
+                array,
+                expression,
+            } => {
+                self.u8(11);
+                self.token(paren);
+                self.expression_id(*array);
+                self.expression_id(*expression);
+            }
+            Stmt::Insert {
+                paren,
+                array,
+                index,
+                expression,
+            } => {
+                self.u8(12);
+                self.token(paren);
+                self.expression_id(*array);
+                self.expression_id(*index);
+                self.expression_id(*expression);
+            }
+            Stmt::Delete {
+                paren,
+                array,
+                index,
+            } => {
+                self.u8(13);
+                self.token(paren);
+                self.expression_id(*array);
+                self.expression_id(*index);
+            }
+        }
+    }
+}
+
+// whether the nodes that `statements` reach form a tree. The engines and the resolver recurse
+// into them, a cycle would never end. Nodes that nothing reaches, like the target an `insert`
+// was parsed from, are never run and may share children.
+fn is_tree(ast: &Ast, statements: &[StmtId]) -> bool {
+    let mut seen_statements = HashSet::new();
+    let mut seen_expressions = HashSet::new();
+    let mut statements = statements.to_vec();
+    let mut expressions = Vec::new();
+    while let Some(id) = statements.pop() {
+        if !seen_statements.insert(id) {
+            return false;
+        }
+        match &ast[id] {
+            Stmt::Block { statements: body } | Stmt::Function { body, .. } => {
+                statements.extend(body);
+            }
+            Stmt::Expression { expression }
+            | Stmt::Print { expression }
+            | Stmt::Return {
+                value: expression, ..
+            }
+            | Stmt::Var {
+                initializer: expression,
+                ..
+            } => expressions.push(*expression),
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                statements.extend(initializer);
+                statements.push(*body);
+                expressions.push(*condition);
+                expressions.extend(increment);
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                statements.push(*then_branch);
+                statements.extend(else_branch);
+                expressions.push(*condition);
+            }
+            Stmt::While { condition, body } => {
+                statements.push(*body);
+                expressions.push(*condition);
+            }
+            Stmt::Append {
+                array, expression, ..
+            } => expressions.extend([*array, *expression]),
+            Stmt::Insert {
+                array,
+                index,
+                expression,
+                ..
+            } => expressions.extend([*array, *index, *expression]),
+            Stmt::Delete { array, index, .. } => expressions.extend([*array, *index]),
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+        }
+    }
+    while let Some(id) = expressions.pop() {
+        if !seen_expressions.insert(id) {
+            return false;
+        }
+        match &ast[id] {
+            Expr::Unary {
+                right: expression, ..
+            }
+            | Expr::Grouping { expression }
+            | Expr::Assign { expression, .. }
+            | Expr::StructGet {
+                target: expression, ..
+            } => expressions.push(*expression),
+            Expr::Binary { left, right, .. }
+            | Expr::Logical { left, right, .. }
+            | Expr::IndexGet {
+                array: left,
+                index: right,
+                ..
+            }
+            | Expr::StructSet {
+                target: left,
+                value: right,
+                ..
+            } => expressions.extend([*left, *right]),
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                expressions.push(*callee);
+                expressions.extend(arguments);
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => expressions.extend([*condition, *then_branch, *else_branch]),
+            Expr::Struct { fields } => expressions.extend(fields.iter().map(|(_, field)| *field)),
+            Expr::Array { elements } => expressions.extend(elements),
+            Expr::IndexSet {
+                array,
+                index,
+                value,
+                ..
+            } => expressions.extend([*array, *index, *value]),
+            Expr::Literal { .. } | Expr::Variable { .. } => {}
+        }
+    }
+    true
+}
+
+// every read fails on malformed input instead of panicking, a broken cache is just not used.
+// Tokens only have the kinds the engines expect where they're used.
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    source: &'a str,
+    offset: usize,
+    expressions: u32,
+    statements: u32,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    fn token(&mut self) -> Option<Token> {
+        let kind = *TOKEN_KINDS.get(self.u8()? as usize)?;
+        let start = self.u32()? as usize;
+        let end = self.u32()? as usize;
+        let line = self.u32()? as usize;
+        let lexeme = self.source.get(start..end)?;
+        let mut token = Token::new(kind, (start + self.offset, end + self.offset), line);
+        if kind == TokenKind::Identifier {
+            token.symbol = Some(Symbol::intern(lexeme));
+        }
+        Some(token)
+    }
+
+    // names are read with `Token::name`, only identifiers have one
+    fn identifier(&mut self) -> Option<Token> {
+        self.token()
+            .filter(|token| token.kind == TokenKind::Identifier)
+    }
+
+    fn identifiers(&mut self) -> Option<Vec<Token>> {
+        (0..self.u32()?).map(|_| self.identifier()).collect()
+    }
+
+    fn operator(&mut self, operators: &[TokenKind]) -> Option<Token> {
+        self.token().filter(|token| operators.contains(&token.kind))
+    }
+
+    fn warning(&mut self) -> Option<Diagnostic> {
+        let code = *WARNING_CODES.get(self.u8()? as usize)?;
+        let warning = Diagnostic::warning(code, self.string()?);
+        match self.u8()? {
+            0 => Some(warning),
+            1 => {
+                let start = self.u32()? as usize;
+                let end = self.u32()? as usize;
+                self.source.get(start..end)?;
+                Some(warning.with_span((start + self.offset, end + self.offset).into()))
+            }
+            _ => None,
+        }
+    }
+
+    fn string(&mut self) -> Option<&'a str> {
+        let len = self.u32()? as usize;
+        std::str::from_utf8(self.take(len)?).ok()
+    }
+
+    fn value(&mut self) -> Option<Value> {
+        match self.u8()? {
+            0 => Some(Value::Nil),
+            1 => Some(Value::Bool(self.bool()?)),
+            2 => Some(Value::Double(f64::from_bits(self.u64()?))),
+            3 => Some(Value::new_string(self.string()?.to_string())),
+            _ => None,
+        }
+    }
+
+    fn expression_id(&mut self) -> Option<ExprId> {
+        let id = self.u32()?;
+        (id < self.expressions).then_some(ExprId(id))
+    }
+
+    fn statement_id(&mut self) -> Option<StmtId> {
+        let id = self.u32()?;
+        (id < self.statements).then_some(StmtId(id))
+    }
+
+    fn expressions_list(&mut self) -> Option<Vec<ExprId>> {
+        (0..self.u32()?).map(|_| self.expression_id()).collect()
+    }
+
+    fn statements_list(&mut self) -> Option<Vec<StmtId>> {
+        (0..self.u32()?).map(|_| self.statement_id()).collect()
+    }
+
+    fn optional_expression(&mut self) -> Option<Option<ExprId>> {
+        match self.expressions_list()?.as_slice() {
+            [] => Some(None),
+            &[id] => Some(Some(id)),
+            _ => None,
+        }
+    }
+
+    fn optional_statement(&mut self) -> Option<Option<StmtId>> {
+        match self.statements_list()?.as_slice() {
+            [] => Some(None),
+            &[id] => Some(Some(id)),
+            _ => None,
+        }
+    }
+
+    fn expression(&mut self) -> Option<Expr> {
+        Some(match self.u8()? {
+            0 => Expr::Unary {
+                operator: self.operator(&UNARY_OPERATORS)?,
+                right: self.expression_id()?,
+            },
+            1 => Expr::Binary {
+                left: self.expression_id()?,
+                operator: self.operator(&BINARY_OPERATORS)?,
+                right: self.expression_id()?,
+            },
+            2 => Expr::Call {
+                callee: self.expression_id()?,
+                paren: self.token()?,
+                arguments: self.expressions_list()?,
+            },
+            3 => Expr::Grouping {
+                expression: self.expression_id()?,
+            },
+            4 => Expr::Literal {
+                value: self.value()?,
+            },
+            5 => Expr::Ternary {
+                condition: self.expression_id()?,
+                then_branch: self.expression_id()?,
+                else_branch: self.expression_id()?,
+            },
+            6 => Expr::Logical {
+                left: self.expression_id()?,
+                operator: self.operator(&LOGICAL_OPERATORS)?,
+                right: self.expression_id()?,
+            },
+            7 => Expr::Variable {
+                name: self.identifier()?,
+                slot: Slot::Global,
+            },
+            8 => Expr::Assign {
+                name: self.identifier()?,
+                expression: self.expression_id()?,
+                slot: Slot::Global,
+            },
+            9 => Expr::Struct {
+                fields: (0..self.u32()?)
+                    .map(|_| Some((self.identifier()?, self.expression_id()?)))
+                    .collect::<Option<_>>()?,
+            },
+            10 => Expr::Array {
+                elements: self.expressions_list()?,
+            },
+            11 => Expr::IndexGet {
+                array: self.expression_id()?,
+                bracket: self.token()?,
+                index: self.expression_id()?,
+            },
+            12 => Expr::IndexSet {
+                array: self.expression_id()?,
+                bracket: self.token()?,
+                index: self.expression_id()?,
+                value: self.expression_id()?,
+            },
+            13 => Expr::StructGet {
+                target: self.expression_id()?,
+                name: self.identifier()?,
+            },
+            14 => Expr::StructSet {
+                target: self.expression_id()?,
+                name: self.identifier()?,
+                value: self.expression_id()?,
+            },
+            _ => return None,
+        })
+    }
+
+    fn statement(&mut self) -> Option<Stmt> {
+        Some(match self.u8()? {
+            0 => Stmt::Block {
+                statements: self.statements_list()?,
+            },
+            1 => Stmt::Expression {
+                expression: self.expression_id()?,
+            },
+            2 => Stmt::For {
+                initializer: self.optional_statement()?,
+                condition: self.expression_id()?,
+                increment: self.optional_expression()?,
+                body: self.statement_id()?,
+            },
+            3 => Stmt::Function {
+                name: self.identifier()?,
+                params: self.identifiers()?,
+                body: self.statements_list()?,
+                slot: Slot::Global,
+            },
+            4 => Stmt::If {
+                condition: self.expression_id()?,
+                then_branch: self.statement_id()?,
+                else_branch: self.optional_statement()?,
+            },
+            5 => Stmt::Print {
+                expression: self.expression_id()?,
+            },
+            6 => Stmt::Return {
+                keyword: self.token()?,
+                value: self.expression_id()?,
+            },
+            7 => Stmt::Break {
+                keyword: self.token()?,
+            },
+            8 => Stmt::Continue {
+                keyword: self.token()?,
+            },
+            9 => Stmt::Var {
+                initializer: self.expression_id()?,
+                name: self.identifier()?,
+                slot: Slot::Global,
+            },
+            10 => Stmt::While {
+                condition: self.expression_id()?,
+                body: self.statement_id()?,
+            },
+            11 => Stmt::Append {
+                paren: self.token()?,
+                array: self.expression_id()?,
+                expression: self.expression_id()?,
+            },
+            12 => Stmt::Insert {
+                paren: self.token()?,
+                array: self.expression_id()?,
+                index: self.expression_id()?,
+                expression: self.expression_id()?,
+            },
+            13 => Stmt::Delete {
+                paren: self.token()?,
+                array: self.expression_id()?,
+                index: self.expression_id()?,
+            },
+            _ => return None,
+        })
+    }
+}
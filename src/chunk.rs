@@ -0,0 +1,72 @@
+use crate::{symbol::Symbol, token::Token, value::Value};
+
+/// Where the container of an `IndexSet` or an `append` was read from. Strings are values, so a
+/// modified string is written back there. `Field` and `Index` keep the struct, or the container
+/// and the index, on the stack below the value they read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Place {
+    Temporary,
+    Local(u32),
+    Global(Symbol),
+    Field(Symbol),
+    Index,
+}
+
+/// A single vm instruction. Operands are indices into the chunk's constants, interned names,
+/// stack slots relative to the current call frame, or absolute jump targets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Constant(u32),
+    Nil,
+    True,
+    False,
+    Pop,
+    PopN(u32),
+    Dup,
+    Dup2,
+    GetLocal(u32),
+    SetLocal(u32),
+    GetGlobal(Symbol),
+    SetGlobal(Symbol),
+    // `x = x + e` with `x` and `e` on the stack, the place is the variable. A string in it
+    // grows in place
+    AddAssign(Place),
+    DefineGlobal(Symbol),
+    // the operator is the kind of the instruction's token
+    Unary,
+    Binary,
+    Jump(u32),
+    // leaves the condition on the stack
+    JumpIfFalse(u32),
+    Call(u32),
+    // a call followed by `Return`, a compiled callee takes over the returning function's frame
+    TailCall(u32),
+    Return,
+    Print,
+    // an empty struct, `Field` then sets its fields one by one
+    Struct,
+    Field(Symbol),
+    Array(u32),
+    StructGet(Symbol),
+    StructSet(Symbol),
+    IndexGet,
+    IndexSet(Place),
+    Append(Place),
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    /// The token every instruction was compiled from, it's the line table and the token runtime
+    /// errors point at.
+    pub tokens: Vec<Token>,
+    pub constants: Vec<Value>,
+}
+
+impl Chunk {
+    pub fn line(&self, offset: usize) -> usize {
+        self.tokens[offset].line
+    }
+}
@@ -0,0 +1,497 @@
+use std::rc::Rc;
+
+use crate::{
+    ast::{Ast, ExprId, StmtId},
+    chunk::{Chunk, Op, Place},
+    environment::Slot,
+    expression::Expr,
+    functions::CompiledFunction,
+    interpreter::self_add,
+    statement::Stmt,
+    symbol::Symbol,
+    token::{Token, TokenKind},
+    value::Value,
+};
+
+#[derive(Default)]
+struct Loop {
+    // locals alive when the body starts, `break` and `continue` pop everything above them
+    locals: u32,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+/// Compiles resolved statements to bytecode for the vm.
+///
+/// Locals live on the vm's stack like in clox: at the start of every statement the stack of the
+/// current frame holds the locals and nothing else, so a new local is simply the value its
+/// initializer left on top. The resolver already decided which names are locals, the compiler only
+/// maps its `(depth, index)` slots to stack slots.
+pub struct Compiler<'a> {
+    ast: &'a Ast,
+    chunk: Chunk,
+    // the stack slot of every local, by scope and by the index the resolver gave it
+    scopes: Vec<Vec<u32>>,
+    locals: u32,
+    loops: Vec<Loop>,
+    // the token of the node being compiled, instructions are attributed to it
+    token: Token,
+}
+
+impl<'a> Compiler<'a> {
+    pub fn new(ast: &'a Ast) -> Self {
+        Self {
+            ast,
+            chunk: Chunk::default(),
+            scopes: Vec::new(),
+            locals: 0,
+            loops: Vec::new(),
+            token: Token::eof(1),
+        }
+    }
+
+    /// Compiles a whole program, the chunk returns `nil` once it ran to the end.
+    pub fn compile(mut self, statements: &[StmtId]) -> Chunk {
+        for &statement in statements {
+            self.statement(statement);
+        }
+        self.emit(Op::Nil);
+        self.emit(Op::Return);
+        self.chunk
+    }
+
+    fn function(&self, name: &Token, params: &[Token], body: &[StmtId]) -> CompiledFunction {
+        // like in the tree walker the body only sees globals and its own locals, the arguments
+        // are the first slots of its frame
+        let mut compiler = Compiler::new(self.ast);
+        let arity = params.len();
+        compiler.scopes.push((0..arity as u32).collect());
+        compiler.locals = arity as u32;
+        compiler.token = params.first().unwrap_or(&self.token).clone();
+        CompiledFunction {
+            name: name.name(),
+            arity,
+            chunk: Rc::new(compiler.compile(body)),
+            declared_at: name.span,
+        }
+    }
+
+    fn statement(&mut self, id: StmtId) {
+        let ast = self.ast;
+        match &ast[id] {
+            Stmt::Print { expression } => {
+                self.expression(*expression);
+                self.emit(Op::Print);
+            }
+            Stmt::Expression { expression } => {
+                self.expression(*expression);
+                self.emit(Op::Pop);
+            }
+            Stmt::Var {
+                name,
+                initializer,
+                slot,
+            } => {
+                self.expression(*initializer);
+                self.token = name.clone();
+                self.define(name.name(), *slot);
+            }
+            Stmt::Block { statements } => {
+                self.scopes.push(Vec::new());
+                for &statement in statements {
+                    self.statement(statement);
+                }
+                let scope = self.scopes.pop().expect("block scope");
+                if !scope.is_empty() {
+                    self.emit(Op::PopN(scope.len() as u32));
+                    self.locals -= scope.len() as u32;
+                }
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(*condition);
+                let else_jump = self.emit(Op::JumpIfFalse(0));
+                self.emit(Op::Pop);
+                self.statement(*then_branch);
+                let end_jump = self.emit(Op::Jump(0));
+                self.patch(else_jump);
+                self.emit(Op::Pop);
+                if let Some(else_branch) = *else_branch {
+                    self.statement(else_branch);
+                }
+                self.patch(end_jump);
+            }
+            Stmt::While { condition, body } => self.compile_loop(*condition, *body, None),
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                if let Some(initializer) = *initializer {
+                    self.statement(initializer);
+                }
+                self.compile_loop(*condition, *body, *increment);
+            }
+            Stmt::Function {
+                name,
+                params,
+                body,
+                slot,
+            } => {
+                let function = self.function(name, params, body);
+                self.constant(Value::new_fun(function));
+                self.define(name.name(), *slot);
+            }
+            Stmt::Return { keyword, value } => {
+                match &ast[*value] {
+                    Expr::Call {
+                        callee,
+                        paren,
+                        arguments,
+                    } => self.call(*callee, paren, arguments, true),
+                    _ => self.expression(*value),
+                }
+                self.token = keyword.clone();
+                self.emit(Op::Return);
+            }
+            Stmt::Break { keyword } => {
+                self.token = keyword.clone();
+                self.pop_loop_locals();
+                let jump = self.emit(Op::Jump(0));
+                self.loops
+                    .last_mut()
+                    .expect("checked by the resolver")
+                    .breaks
+                    .push(jump);
+            }
+            Stmt::Continue { keyword } => {
+                self.token = keyword.clone();
+                self.pop_loop_locals();
+                let jump = self.emit(Op::Jump(0));
+                self.loops
+                    .last_mut()
+                    .expect("checked by the resolver")
+                    .continues
+                    .push(jump);
+            }
+            Stmt::Append {
+                paren,
+                array,
+                expression,
+            } => {
+                self.expression(*expression);
+                let place = self.place(*array);
+                self.token = paren.clone();
+                self.emit(Op::Append(place));
+            }
+            Stmt::Insert {
+                paren,
+                array,
+                index,
+                expression,
+            } => {
+                self.expression(*array);
+                self.expression(*expression);
+                self.expression(*index);
+                self.token = paren.clone();
+                self.emit(Op::Insert);
+            }
+            Stmt::Delete {
+                paren,
+                array,
+                index,
+            } => {
+                self.expression(*array);
+                self.expression(*index);
+                self.token = paren.clone();
+                self.emit(Op::Delete);
+            }
+        }
+    }
+
+    fn compile_loop(&mut self, condition: ExprId, body: StmtId, increment: Option<ExprId>) {
+        let start = self.chunk.code.len();
+        self.expression(condition);
+        let exit_jump = self.emit(Op::JumpIfFalse(0));
+        self.emit(Op::Pop);
+        self.loops.push(Loop {
+            locals: self.locals,
+            ..Loop::default()
+        });
+        self.statement(body);
+        let lp = self.loops.pop().expect("loop");
+        for jump in lp.continues {
+            self.patch(jump);
+        }
+        if let Some(increment) = increment {
+            self.expression(increment);
+            self.emit(Op::Pop);
+        }
+        self.emit(Op::Jump(start as u32));
+        self.patch(exit_jump);
+        self.emit(Op::Pop);
+        for jump in lp.breaks {
+            self.patch(jump);
+        }
+    }
+
+    fn pop_loop_locals(&mut self) {
+        let lp = self.loops.last().expect("checked by the resolver");
+        let count = self.locals - lp.locals;
+        if count > 0 {
+            self.emit(Op::PopN(count));
+        }
+    }
+
+    fn expression(&mut self, id: ExprId) {
+        let ast = self.ast;
+        match &ast[id] {
+            Expr::Unary { operator, right } => {
+                self.expression(*right);
+                self.token = operator.clone();
+                self.emit(Op::Unary);
+            }
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(*left);
+                if operator.kind == TokenKind::Comma {
+                    self.emit(Op::Pop);
+                    self.expression(*right);
+                } else {
+                    self.expression(*right);
+                    self.token = operator.clone();
+                    self.emit(Op::Binary);
+                }
+            }
+            Expr::Grouping { expression } => self.expression(*expression),
+            Expr::Literal { value } => match value {
+                Value::Nil => _ = self.emit(Op::Nil),
+                Value::Bool(true) => _ = self.emit(Op::True),
+                Value::Bool(false) => _ = self.emit(Op::False),
+                value => self.constant(value.clone()),
+            },
+            Expr::Variable { name, slot } => {
+                self.token = name.clone();
+                let op = match *slot {
+                    Slot::Global => Op::GetGlobal(name.name()),
+                    Slot::Local { depth, index } => Op::GetLocal(self.local(depth, index)),
+                };
+                self.emit(op);
+            }
+            Expr::Assign {
+                name,
+                expression,
+                slot,
+            } => {
+                if let Some((left, operator, right)) = self_add(ast, name, *slot, *expression) {
+                    let place = self.place(left);
+                    self.expression(right);
+                    self.token = operator.clone();
+                    self.emit(Op::AddAssign(place));
+                    return;
+                }
+                self.expression(*expression);
+                self.token = name.clone();
+                let op = match *slot {
+                    Slot::Global => Op::SetGlobal(name.name()),
+                    Slot::Local { depth, index } => Op::SetLocal(self.local(depth, index)),
+                };
+                self.emit(op);
+            }
+            Expr::Logical {
+                left,
+                operator,
+                right,
+            } => {
+                self.expression(*left);
+                let jump = match operator.kind {
+                    TokenKind::And => self.emit(Op::JumpIfFalse(0)),
+                    TokenKind::Or => {
+                        let else_jump = self.emit(Op::JumpIfFalse(0));
+                        let end_jump = self.emit(Op::Jump(0));
+                        self.patch(else_jump);
+                        end_jump
+                    }
+                    _ => unreachable!("Invalid logical operator: {operator:?}"),
+                };
+                self.emit(Op::Pop);
+                self.expression(*right);
+                self.patch(jump);
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => self.call(*callee, paren, arguments, false),
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(*condition);
+                let else_jump = self.emit(Op::JumpIfFalse(0));
+                self.emit(Op::Pop);
+                self.expression(*then_branch);
+                let end_jump = self.emit(Op::Jump(0));
+                self.patch(else_jump);
+                self.emit(Op::Pop);
+                self.expression(*else_branch);
+                self.patch(end_jump);
+            }
+            Expr::Struct { fields } => {
+                self.emit(Op::Struct);
+                for &(ref name, value) in fields {
+                    self.expression(value);
+                    self.token = name.clone();
+                    self.emit(Op::Field(name.name()));
+                }
+            }
+            Expr::StructGet { target, name } => {
+                self.expression(*target);
+                self.token = name.clone();
+                self.emit(Op::StructGet(name.name()));
+            }
+            Expr::StructSet {
+                target,
+                name,
+                value,
+            } => {
+                self.expression(*target);
+                self.expression(*value);
+                self.token = name.clone();
+                self.emit(Op::StructSet(name.name()));
+            }
+            Expr::Array { elements } => {
+                for &element in elements {
+                    self.expression(element);
+                }
+                self.emit(Op::Array(elements.len() as u32));
+            }
+            Expr::IndexGet {
+                array,
+                bracket,
+                index,
+            } => {
+                self.expression(*array);
+                self.expression(*index);
+                self.token = bracket.clone();
+                self.emit(Op::IndexGet);
+            }
+            Expr::IndexSet {
+                array,
+                bracket,
+                index,
+                value,
+            } => {
+                let place = self.place(*array);
+                self.expression(*index);
+                self.expression(*value);
+                self.token = bracket.clone();
+                self.emit(Op::IndexSet(place));
+            }
+        }
+    }
+
+    fn call(&mut self, callee: ExprId, paren: &Token, arguments: &[ExprId], tail: bool) {
+        self.expression(callee);
+        for &argument in arguments {
+            self.expression(argument);
+        }
+        self.token = paren.clone();
+        let count = arguments.len() as u32;
+        self.emit(if tail {
+            Op::TailCall(count)
+        } else {
+            Op::Call(count)
+        });
+    }
+
+    // compiles an expression that a string might have to be written back to, see `Place`
+    fn place(&mut self, id: ExprId) -> Place {
+        let ast = self.ast;
+        match &ast[id] {
+            Expr::Variable { name, slot } => {
+                self.expression(id);
+                match *slot {
+                    Slot::Global => Place::Global(name.name()),
+                    Slot::Local { depth, index } => Place::Local(self.local(depth, index)),
+                }
+            }
+            Expr::StructGet { target, name } => {
+                self.expression(*target);
+                self.emit(Op::Dup);
+                self.token = name.clone();
+                self.emit(Op::StructGet(name.name()));
+                Place::Field(name.name())
+            }
+            Expr::IndexGet {
+                array,
+                bracket,
+                index,
+            } => {
+                self.expression(*array);
+                self.expression(*index);
+                self.emit(Op::Dup2);
+                self.token = bracket.clone();
+                self.emit(Op::IndexGet);
+                Place::Index
+            }
+            _ => {
+                self.expression(id);
+                Place::Temporary
+            }
+        }
+    }
+
+    fn define(&mut self, name: Symbol, slot: Slot) {
+        match slot {
+            Slot::Global => {
+                self.emit(Op::DefineGlobal(name));
+            }
+            Slot::Local { index, .. } => {
+                let scope = self.scopes.last_mut().expect("locals live in a scope");
+                if index == scope.len() {
+                    // the value on top of the stack becomes the local
+                    scope.push(self.locals);
+                    self.locals += 1;
+                } else {
+                    let slot = scope[index];
+                    self.emit(Op::SetLocal(slot));
+                    self.emit(Op::Pop);
+                }
+            }
+        }
+    }
+
+    fn local(&self, depth: usize, index: usize) -> u32 {
+        self.scopes[self.scopes.len() - 1 - depth][index]
+    }
+
+    fn constant(&mut self, value: Value) {
+        let index = self.chunk.constants.len() as u32;
+        self.chunk.constants.push(value);
+        self.emit(Op::Constant(index));
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.tokens.push(self.token.clone());
+        self.chunk.code.len() - 1
+    }
+
+    // points the jump at `offset` to the next instruction
+    fn patch(&mut self, offset: usize) {
+        let target = self.chunk.code.len() as u32;
+        match &mut self.chunk.code[offset] {
+            Op::Jump(to) | Op::JumpIfFalse(to) => *to = target,
+            op => unreachable!("{op:?} is not a jump"),
+        }
+    }
+}
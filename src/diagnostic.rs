@@ -0,0 +1,414 @@
+use std::{
+    fmt::{Display, Write},
+    io::{self, IsTerminal},
+};
+
+use crate::token::{Span, Token};
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+// tabs in a source line are shown as this many spaces so the carets line up
+const TAB_WIDTH: usize = 4;
+
+/// How diagnostics are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ErrorFormat {
+    /// The source with carets under the error, for people.
+    #[default]
+    Human,
+    /// One JSON object per line, for tools.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    /// Something that runs but is probably a mistake, it doesn't stop the program.
+    Warning,
+}
+
+impl Severity {
+    fn color(self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => f.write_str("error"),
+            Severity::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+/// What went wrong, as a stable code tools can match on. The codes never change meaning, new
+/// kinds of errors get new codes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Code {
+    UnexpectedCharacter,
+    UnterminatedString,
+    UnterminatedComment,
+    Syntax,
+    ExpectedToken,
+    ExpectedExpression,
+    InvalidTarget,
+    TopLevelReturn,
+    BreakOutsideLoop,
+    ContinueOutsideLoop,
+    OwnInitializer,
+    Runtime,
+    Stopped,
+    UndefinedVariable,
+    TypeMismatch,
+    IndexOutOfBounds,
+    Arity,
+    RecursionDepth,
+    MemoryLimit,
+    MissingField,
+    UnusedVariable,
+    UnusedParameter,
+    Redeclaration,
+    ShadowedNative,
+    Unreachable,
+    UndeclaredAssignment,
+    ArgumentCount,
+    InvalidEscape,
+}
+
+impl Code {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Code::UnexpectedCharacter => "E0001",
+            Code::UnterminatedString => "E0002",
+            Code::UnterminatedComment => "E0003",
+            Code::Syntax => "E0100",
+            Code::ExpectedToken => "E0101",
+            Code::ExpectedExpression => "E0102",
+            Code::InvalidTarget => "E0103",
+            Code::TopLevelReturn => "E0200",
+            Code::BreakOutsideLoop => "E0201",
+            Code::ContinueOutsideLoop => "E0202",
+            Code::OwnInitializer => "E0203",
+            Code::Runtime => "E0300",
+            Code::Stopped => "E0301",
+            Code::UndefinedVariable => "E0302",
+            Code::TypeMismatch => "E0303",
+            Code::IndexOutOfBounds => "E0304",
+            Code::Arity => "E0305",
+            Code::RecursionDepth => "E0306",
+            Code::MemoryLimit => "E0307",
+            Code::MissingField => "E0308",
+            Code::UnusedVariable => "W0001",
+            Code::UnusedParameter => "W0002",
+            Code::Redeclaration => "W0003",
+            Code::ShadowedNative => "W0004",
+            Code::Unreachable => "W0005",
+            Code::UndeclaredAssignment => "W0006",
+            Code::ArgumentCount => "W0007",
+            Code::InvalidEscape => "W0008",
+        }
+    }
+}
+
+/// A secondary span of a diagnostic, like the place a function was defined.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+/// An error or a warning in a program, shown with the source it points at.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Code,
+    pub message: String,
+    pub span: Option<Span>,
+    pub labels: Vec<Label>,
+    /// Lines shown after the source, like the calls that led to the error.
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: Code, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn warning(code: Code, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(code, message)
+        }
+    }
+
+    pub fn at(mut self, token: Option<&Token>) -> Self {
+        self.span = token.map(|token| token.span);
+        self
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_labels(mut self, labels: Vec<Label>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    pub fn with_notes(mut self, notes: Vec<String>) -> Self {
+        self.notes = notes;
+        self
+    }
+
+    /// Prints the diagnostic to stderr, in colour if it's a terminal. `file` is the script the
+    /// source was read from.
+    pub fn emit(&self, source: &str, file: Option<&str>, format: ErrorFormat) {
+        match format {
+            ErrorFormat::Human => {
+                eprint!("{}", self.render(source, file, io::stderr().is_terminal()))
+            }
+            ErrorFormat::Json => eprintln!("{}", self.to_json(source, file)),
+        }
+    }
+
+    /// The message, where it happened and every line it points at, with `^` under the error and
+    /// `-` under the labels, then the notes.
+    pub fn render(&self, source: &str, file: Option<&str>, color: bool) -> String {
+        let paint = |color_code: &'static str| if color { color_code } else { "" };
+        let reset = paint(RESET);
+
+        let mut out = format!(
+            "{}{}[{}]{reset}{}: {}{reset}\n",
+            paint(self.severity.color()),
+            self.severity,
+            self.code.as_str(),
+            paint(BOLD),
+            self.message
+        );
+        let Some(span) = self.span else {
+            self.render_notes(&mut out, 1, color);
+            return out;
+        };
+
+        let mut marks: Vec<(Location, bool, &str)> = vec![(Location::new(source, span), true, "")];
+        marks.extend(
+            self.labels
+                .iter()
+                .map(|label| (Location::new(source, label.span), false, &*label.message)),
+        );
+        marks.sort_by_key(|(location, primary, _)| (location.line, location.column, !primary));
+
+        let width = marks
+            .iter()
+            .map(|(location, ..)| location.line)
+            .max()
+            .unwrap_or(1);
+        let width = width.to_string().len();
+        let gutter = format!("{}{:width$} |{reset}", paint(BLUE), "");
+
+        let (line, column) = line_column(source, span.start);
+        let file = file.map_or(String::new(), |file| format!("{file}:"));
+        _ = writeln!(
+            out,
+            "{}{:width$}--> {reset}{file}{line}:{column}",
+            paint(BLUE),
+            ""
+        );
+        _ = writeln!(out, "{gutter}");
+
+        let mut previous_line = None;
+        for (location, primary, message) in &marks {
+            if previous_line != Some(location.line) {
+                if previous_line.is_some_and(|previous| previous + 1 < location.line) {
+                    _ = writeln!(out, "{}...{reset}", paint(BLUE));
+                }
+                _ = writeln!(
+                    out,
+                    "{}{:>width$} |{reset} {}",
+                    paint(BLUE),
+                    location.line,
+                    expand_tabs(location.text)
+                );
+                previous_line = Some(location.line);
+            }
+            let (marker, color_code) = if *primary {
+                ('^', self.severity.color())
+            } else {
+                ('-', BLUE)
+            };
+            let padding = display_width(&location.text[..location.start]);
+            let underline = display_width(&location.text[location.start..location.end]).max(1);
+            _ = write!(
+                out,
+                "{gutter} {:padding$}{}{}",
+                "",
+                paint(color_code),
+                marker.to_string().repeat(underline)
+            );
+            if !message.is_empty() {
+                _ = write!(out, " {message}");
+            }
+            _ = writeln!(out, "{reset}");
+        }
+        self.render_notes(&mut out, width, color);
+        out
+    }
+
+    /// The diagnostic as a JSON object on one line. Spans are byte offsets into the source, lines
+    /// and columns start at 1 and columns count characters.
+    pub fn to_json(&self, source: &str, file: Option<&str>) -> String {
+        let mut out = format!(
+            "{{\"severity\":\"{}\",\"code\":\"{}\",\"message\":{},\"file\":",
+            self.severity,
+            self.code.as_str(),
+            json_string(&self.message)
+        );
+        match file {
+            Some(file) => out.push_str(&json_string(file)),
+            None => out.push_str("null"),
+        }
+        match self.span {
+            Some(span) => write_json_span(&mut out, source, span),
+            None => out.push_str(",\"span\":null,\"line\":null,\"column\":null"),
+        }
+        out.push_str(",\"labels\":[");
+        for (i, label) in self.labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            _ = write!(out, "{{\"message\":{}", json_string(&label.message));
+            write_json_span(&mut out, source, label.span);
+            out.push('}');
+        }
+        out.push_str("],\"notes\":[");
+        for (i, note) in self.notes.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(&json_string(note));
+        }
+        out.push_str("]}");
+        out
+    }
+
+    fn render_notes(&self, out: &mut String, width: usize, color: bool) {
+        for note in &self.notes {
+            if color {
+                _ = writeln!(out, "{BLUE}{:width$} ={RESET} {note}", "");
+            } else {
+                _ = writeln!(out, "{:width$} = {note}", "");
+            }
+        }
+    }
+}
+
+/// The line and column of a byte offset, both starting at 1. Columns count characters.
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let offset = floor_char_boundary(source, offset);
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (line, source[line_start..offset].chars().count() + 1)
+}
+
+// the line a span starts on, and the part of it the span covers. A span that goes past the end
+// of its line is cut there.
+struct Location<'a> {
+    line: usize,
+    column: usize,
+    text: &'a str,
+    start: usize,
+    end: usize,
+}
+
+impl<'a> Location<'a> {
+    fn new(source: &'a str, span: Span) -> Self {
+        let offset = floor_char_boundary(source, span.start);
+        let (line, column) = line_column(source, offset);
+        let line_start = source[..offset]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        let line_end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |newline| offset + newline);
+        let end = floor_char_boundary(source, span.end.clamp(offset, line_end));
+        let text = source[line_start..line_end].trim_end_matches('\r');
+        Self {
+            line,
+            column,
+            text,
+            start: offset - line_start,
+            end: (end - line_start).min(text.len()),
+        }
+    }
+}
+
+fn write_json_span(out: &mut String, source: &str, span: Span) {
+    let (line, column) = line_column(source, span.start);
+    _ = write!(
+        out,
+        ",\"span\":{{\"start\":{},\"end\":{}}},\"line\":{line},\"column\":{column}",
+        span.start, span.end
+    );
+}
+
+fn json_string(string: &str) -> String {
+    let mut out = String::with_capacity(string.len() + 2);
+    out.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => _ = write!(out, "\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn floor_char_boundary(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum()
+}
@@ -1,6 +1,18 @@
 use ahash::AHashMap as HashMap;
 
-use crate::value::Value;
+use crate::{symbol::Symbol, value::Value};
+
+/// Where a variable lives, filled in by the resolver. Locals are `index` in the scope `depth`
+/// scopes up from the innermost one, globals are looked up by name.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Slot {
+    #[default]
+    Global,
+    Local {
+        depth: usize,
+        index: usize,
+    },
+}
 
 #[derive(Debug, Default)]
 pub struct Environment {
@@ -12,36 +24,68 @@ impl Environment {
         Self { ids }
     }
 
-    pub fn get(&self, name: &str, environments: &[HashMap<String, Value>]) -> Option<Value> {
-        for &id in self.ids.iter().rev() {
-            if let Some(value) = environments[id].get(name) {
-                return Some(value.clone());
-            }
+    pub fn get(
+        &self,
+        name: Symbol,
+        slot: Slot,
+        globals: &HashMap<Symbol, Value>,
+        environments: &[Vec<Value>],
+    ) -> Option<Value> {
+        match slot {
+            Slot::Global => globals.get(&name).cloned(),
+            Slot::Local { depth, index } => environments[self.id(depth)].get(index).cloned(),
         }
-        None
     }
 
     pub fn assign(
         &mut self,
-        name: &str,
+        name: Symbol,
+        slot: Slot,
         value: Value,
-        environments: &mut [HashMap<String, Value>],
+        globals: &mut HashMap<Symbol, Value>,
+        environments: &mut [Vec<Value>],
     ) -> Option<Value> {
-        for &id in self.ids.iter().rev() {
-            if let Some(old_value) = environments[id].get_mut(name) {
-                *old_value = value.clone();
-                return Some(value);
-            }
+        let old_value = self.get_mut(name, slot, globals, environments)?;
+        *old_value = value.clone();
+        Some(value)
+    }
+
+    pub fn get_mut<'a>(
+        &self,
+        name: Symbol,
+        slot: Slot,
+        globals: &'a mut HashMap<Symbol, Value>,
+        environments: &'a mut [Vec<Value>],
+    ) -> Option<&'a mut Value> {
+        match slot {
+            Slot::Global => globals.get_mut(&name),
+            Slot::Local { depth, index } => environments[self.id(depth)].get_mut(index),
         }
-        None
     }
 
     pub fn define(
         &mut self,
-        name: String,
+        name: Symbol,
+        slot: Slot,
         value: Value,
-        environments: &mut [HashMap<String, Value>],
+        globals: &mut HashMap<Symbol, Value>,
+        environments: &mut [Vec<Value>],
     ) {
-        environments[*self.ids.last().unwrap()].insert(name, value);
+        match slot {
+            Slot::Global => {
+                globals.insert(name, value);
+            }
+            Slot::Local { depth, index } => {
+                let values = &mut environments[self.id(depth)];
+                if index >= values.len() {
+                    values.resize(index + 1, Value::Nil);
+                }
+                values[index] = value;
+            }
+        }
+    }
+
+    fn id(&self, depth: usize) -> usize {
+        self.ids[self.ids.len() - 1 - depth]
     }
 }
@@ -1,71 +1,75 @@
 #![allow(non_snake_case)]
-use crate::{generate_enum_and_functions, token::Token, value::Value};
+use crate::{
+    ast::ExprId, environment::Slot, generate_enum_and_functions, token::Token, value::Value,
+};
 
 generate_enum_and_functions! {
     Expr {
         Unary {
             operator: Token,
-            right: Expr,
+            right: ExprId,
         },
         Binary {
-            left: Expr,
+            left: ExprId,
             operator: Token,
-            right: Expr,
+            right: ExprId,
         },
         Call {
-            callee: Expr,
+            callee: ExprId,
             paren: Token,
-            arguments: Vec<Expr>,
+            arguments: Vec<ExprId>,
         },
         Grouping {
-            expression: Expr,
+            expression: ExprId,
         },
         Literal {
             value: Value,
         },
         Ternary {
-            condition: Expr,
-            then_branch: Expr,
-            else_branch: Expr,
+            condition: ExprId,
+            then_branch: ExprId,
+            else_branch: ExprId,
         },
         Logical {
-            left: Expr,
+            left: ExprId,
             operator: Token,
-            right: Expr,
+            right: ExprId,
         },
         Variable {
             name: Token,
+            slot: Slot,
         },
         Assign {
             name: Token,
-            expression: Expr,
+            expression: ExprId,
+            slot: Slot,
         },
         // TODO: maybe rename this to map or something
         Struct {
-            fields: Vec<(Token, Expr)>
+            fields: Vec<(Token, ExprId)>
         },
         Array {
-            elements: Vec<Expr>
+            elements: Vec<ExprId>
         },
         IndexGet {
-            array: Expr,
+            array: ExprId,
             bracket: Token,
-            index: Expr,
+            index: ExprId,
         },
         IndexSet {
-            array: Expr,
+            array: ExprId,
             bracket: Token,
-            index: Expr,
-            value: Expr,
+            index: ExprId,
+            value: ExprId,
         },
         StructGet {
-            target: Expr,
+            target: ExprId,
             name: Token,
         },
         StructSet {
-            target: Expr,
+            target: ExprId,
             name: Token,
-            value: Expr,
+            value: ExprId,
         }
     }
 }
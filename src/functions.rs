@@ -1,30 +1,77 @@
-use crate::{interpreter::Interpreter, statement::Stmt, token::Token, value::Value, IntError};
-use ahash::AHashMap as HashMap;
-use std::fmt::Debug;
+use crate::{
+    ast::{Ast, StmtId},
+    chunk::Chunk,
+    interpreter::Interpreter,
+    statement::Stmt,
+    symbol::Symbol,
+    token::Span,
+    value::Value,
+    vm::Vm,
+    IntError,
+};
+use std::{fmt::Debug, rc::Rc};
 
 pub trait IntCallable {
     fn arity(&self) -> usize;
     fn name(&self) -> String;
     fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>)
         -> Result<Value, IntError>;
+    /// The bytecode of a compiled function, the vm runs it in its own loop instead of calling
+    /// `call`.
+    fn chunk(&self) -> Option<&Rc<Chunk>> {
+        None
+    }
+    /// Script functions, tail calls to them reuse the caller's frame.
+    fn as_function(&self) -> Option<&Function> {
+        None
+    }
+    /// The name in the declaration of a script function.
+    fn declared_at(&self) -> Option<Span> {
+        None
+    }
+    /// The plain name, as tracebacks show it.
+    fn trace_name(&self) -> String {
+        let name = self.name();
+        let name = name.strip_prefix("<fun ").unwrap_or(&name);
+        name.strip_suffix('>').unwrap_or(name).to_string()
+    }
 }
 
+/// A function of the tree walker. It keeps the program it was declared in alive, its body is
+/// never copied.
 #[derive(Clone, Debug)]
 pub struct Function {
-    pub name: String,
-    params: Vec<Token>,
-    body: Vec<Stmt>,
+    pub name: Symbol,
+    arity: usize,
+    ast: Rc<Ast>,
+    // the `Stmt::Function` it was made from
+    declaration: StmtId,
 }
 
 impl Function {
-    pub fn new(name: String, params: Vec<Token>, body: Vec<Stmt>) -> Self {
-        Self { name, params, body }
+    pub fn new(ast: Rc<Ast>, declaration: StmtId) -> Self {
+        let Stmt::Function { name, params, .. } = &ast[declaration] else {
+            panic!("a function is made from a function declaration");
+        };
+        Self {
+            name: name.name(),
+            arity: params.len(),
+            declaration,
+            ast,
+        }
+    }
+
+    fn body(&self) -> &[StmtId] {
+        match &self.ast[self.declaration] {
+            Stmt::Function { body, .. } => body,
+            _ => unreachable!("checked in `new`"),
+        }
     }
 }
 
 impl IntCallable for Function {
     fn arity(&self) -> usize {
-        self.params.len()
+        self.arity
     }
 
     fn name(&self) -> String {
@@ -36,22 +83,95 @@ impl IntCallable for Function {
         interpreter: &mut Interpreter,
         arguments: Vec<Value>,
     ) -> Result<Value, IntError> {
-        let mut values = HashMap::new();
-        for (token, argument) in self.params.clone().into_iter().zip(arguments) {
-            values.insert(interpreter.lexeme(&token).to_string(), argument);
+        // the arguments are the first slots of the function's scope, and functions only see
+        // globals besides their own locals
+        let mut result = interpreter.execute_block(&self.ast, self.body(), &[], arguments);
+        loop {
+            return match result {
+                Ok(()) => Ok(Value::Nil),
+                Err(IntError::ReturnValue(value, _)) => Ok(value),
+                Err(IntError::TailCall(fun, arguments)) => {
+                    // the function that made the call has returned, run the callee in its place
+                    interpreter.replace_frame(&fun);
+                    match fun.0.as_function() {
+                        Some(function) => {
+                            result = interpreter.execute_block(
+                                &function.ast,
+                                function.body(),
+                                &[],
+                                arguments,
+                            );
+                            continue;
+                        }
+                        None => fun.0.call(interpreter, arguments),
+                    }
+                }
+                Err(err @ (IntError::Error { .. } | IntError::Stopped(_) | IntError::Exit(_))) => {
+                    Err(err)
+                }
+                Err(IntError::Break(keyword)) => Err(IntError::new(
+                    Some(&keyword),
+                    "break is only allowed in loops.",
+                )),
+                Err(IntError::Continue(keyword)) => Err(IntError::new(
+                    Some(&keyword),
+                    "continue is only allowed in loops.",
+                )),
+            };
         }
-        match interpreter.execute_block(&self.body, &[0], values) {
-            Ok(()) => Ok(Value::Nil),
-            Err(IntError::ReturnValue(value, _)) => Ok(value),
-            Err(err @ IntError::Error { .. }) => Err(err),
-            Err(IntError::Break(keyword)) => Err(IntError::Error {
-                message: "break is only allowed in loops.".into(),
-                token: Some(keyword),
-            }),
-            Err(IntError::Continue(keyword)) => Err(IntError::Error {
-                message: "continue is only allowed in loops.".into(),
-                token: Some(keyword),
-            }),
+    }
+
+    fn as_function(&self) -> Option<&Function> {
+        Some(self)
+    }
+
+    fn trace_name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn declared_at(&self) -> Option<Span> {
+        match &self.ast[self.declaration] {
+            Stmt::Function { name, .. } => Some(name.span),
+            _ => unreachable!("checked in `new`"),
         }
     }
 }
+
+/// A function compiled to bytecode for the vm.
+#[derive(Debug)]
+pub struct CompiledFunction {
+    pub name: Symbol,
+    pub(crate) arity: usize,
+    pub(crate) chunk: Rc<Chunk>,
+    pub(crate) declared_at: Span,
+}
+
+impl IntCallable for CompiledFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn name(&self) -> String {
+        format!("<fn {} >", self.name)
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, IntError> {
+        Vm::new(interpreter).run(self.chunk.clone(), arguments)
+    }
+
+    fn chunk(&self) -> Option<&Rc<Chunk>> {
+        Some(&self.chunk)
+    }
+
+    fn trace_name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn declared_at(&self) -> Option<Span> {
+        Some(self.declared_at)
+    }
+}
@@ -0,0 +1,210 @@
+use ahash::AHashMap as HashMap;
+use std::{
+    cell::RefCell,
+    mem,
+    rc::{Rc, Weak},
+};
+
+use crate::value::{Object, StructMap, Value};
+
+// collections are triggered once this many containers are tracked, the threshold then grows with
+// the number of containers that survived.
+const MIN_THRESHOLD: usize = 10_000;
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::default());
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GcStats {
+    pub tracked: usize,
+    pub collections: usize,
+    pub collected: usize,
+}
+
+enum Tracked {
+    Struct(Weak<RefCell<StructMap>>),
+    Array(Weak<RefCell<Vec<Value>>>),
+}
+
+impl Tracked {
+    fn upgrade(&self) -> Option<Object> {
+        match self {
+            Tracked::Struct(map) => map.upgrade().map(Object::Struct),
+            Tracked::Array(array) => array.upgrade().map(Object::Array),
+        }
+    }
+}
+
+struct Heap {
+    objects: Vec<Tracked>,
+    threshold: usize,
+    collections: usize,
+    collected: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            objects: Vec::new(),
+            threshold: MIN_THRESHOLD,
+            collections: 0,
+            collected: 0,
+        }
+    }
+}
+
+/// Registers a struct or an array with the cycle collector, strings can't hold references so they
+/// are never tracked. May run a collection if enough containers were allocated since the last one.
+pub fn track(object: &Object) {
+    let tracked = match object {
+        Object::Struct(map) => Tracked::Struct(Rc::downgrade(map)),
+        Object::Array(array) => Tracked::Array(Rc::downgrade(array)),
+        Object::String(_) | Object::Builder(_) => return,
+    };
+    let should_collect = HEAP.with_borrow_mut(|heap| {
+        heap.objects.push(tracked);
+        heap.objects.len() >= heap.threshold
+    });
+    if should_collect {
+        collect();
+    }
+}
+
+pub fn stats() -> GcStats {
+    HEAP.with_borrow(|heap| GcStats {
+        tracked: heap.objects.len(),
+        collections: heap.collections,
+        collected: heap.collected,
+    })
+}
+
+/// Frees every struct and array that is only reachable through reference cycles and returns how
+/// many were freed.
+///
+/// There is no explicit root set: an object whose strong count is higher than the number of
+/// references coming from other tracked objects is referenced from the outside (the
+/// interpreter's environments, a value on the Rust stack, ...), so it's a root. Everything
+/// reachable from a root survives, the rest is garbage and gets its contents cleared, which
+/// breaks the cycles and lets `Rc` free it.
+pub fn collect() -> usize {
+    let tracked = HEAP.with_borrow_mut(|heap| mem::take(&mut heap.objects));
+    let objects: Vec<Object> = tracked.iter().filter_map(Tracked::upgrade).collect();
+    drop(tracked);
+
+    let index: HashMap<usize, usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(i, object)| (object.addr(), i))
+        .collect();
+
+    // the `- 1` is the strong reference that `objects` itself holds
+    let mut external: Vec<isize> = objects
+        .iter()
+        .map(|object| strong_count(object) as isize - 1)
+        .collect();
+    // objects that are borrowed right now can't be inspected, they're kept alive and their
+    // children count as referenced from the outside
+    let mut opaque = vec![false; objects.len()];
+    for (i, object) in objects.iter().enumerate() {
+        let visited = for_each_child(object, |child| {
+            if let Some(&child) = index.get(&child.addr()) {
+                external[child] -= 1;
+            }
+        });
+        opaque[i] = !visited;
+    }
+
+    let mut reachable = vec![false; objects.len()];
+    let mut stack: Vec<usize> = (0..objects.len())
+        .filter(|&i| external[i] > 0 || opaque[i])
+        .collect();
+    while let Some(i) = stack.pop() {
+        if mem::replace(&mut reachable[i], true) {
+            continue;
+        }
+        for_each_child(&objects[i], |child| {
+            if let Some(&child) = index.get(&child.addr()) {
+                if !reachable[child] {
+                    stack.push(child);
+                }
+            }
+        });
+    }
+
+    let mut garbage = Vec::new();
+    for (object, &reachable) in objects.iter().zip(&reachable) {
+        if !reachable {
+            garbage.push(clear(object));
+        }
+    }
+    let collected = garbage.len();
+
+    let survivors: Vec<Tracked> = objects
+        .iter()
+        .zip(&reachable)
+        .filter(|(_, &reachable)| reachable)
+        .map(|(object, _)| match object {
+            Object::Struct(map) => Tracked::Struct(Rc::downgrade(map)),
+            Object::Array(array) => Tracked::Array(Rc::downgrade(array)),
+            Object::String(_) | Object::Builder(_) => unreachable!("strings are never tracked"),
+        })
+        .collect();
+    HEAP.with_borrow_mut(|heap| {
+        // objects allocated while the contents were dropped
+        let allocated = mem::replace(&mut heap.objects, survivors);
+        heap.objects.extend(allocated);
+        heap.threshold = MIN_THRESHOLD.max(heap.objects.len() * 2);
+        heap.collections += 1;
+        heap.collected += collected;
+    });
+
+    drop(objects);
+    drop(garbage);
+    collected
+}
+
+fn strong_count(object: &Object) -> usize {
+    match object {
+        Object::String(string) => Rc::strong_count(string),
+        Object::Builder(builder) => Rc::strong_count(builder),
+        Object::Struct(map) => Rc::strong_count(map),
+        Object::Array(array) => Rc::strong_count(array),
+    }
+}
+
+// returns false if the object is mutably borrowed and couldn't be visited
+fn for_each_child(object: &Object, mut visit: impl FnMut(&Object)) -> bool {
+    let mut visit_value = |value: &Value| {
+        if let Value::Object(child) = value {
+            visit(child);
+        }
+    };
+    match object {
+        Object::String(_) | Object::Builder(_) => true,
+        Object::Struct(map) => map
+            .try_borrow()
+            .map(|map| map.values().for_each(&mut visit_value))
+            .is_ok(),
+        Object::Array(array) => array
+            .try_borrow()
+            .map(|array| array.iter().for_each(&mut visit_value))
+            .is_ok(),
+    }
+}
+
+// takes the contents out of a garbage object, they're dropped by the caller once the heap isn't
+// borrowed anymore
+fn clear(object: &Object) -> Vec<Value> {
+    match object {
+        Object::String(_) | Object::Builder(_) => Vec::new(),
+        Object::Struct(map) => map
+            .try_borrow_mut()
+            .map(|mut map| mem::take(&mut *map).into_values().collect())
+            .unwrap_or_default(),
+        Object::Array(array) => array
+            .try_borrow_mut()
+            .map(|mut array| mem::take(&mut *array))
+            .unwrap_or_default(),
+    }
+}
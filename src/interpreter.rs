@@ -1,27 +1,111 @@
 use ahash::AHashMap as HashMap;
-use std::{iter::once, mem};
+use std::{
+    borrow::Cow,
+    fs, io,
+    iter::once,
+    mem::{self, size_of},
+    path::Path,
+    rc::Rc,
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
 
 use crate::{
-    environment::Environment,
+    ast::{Ast, ExprId, Program, StmtId},
+    budget::{Budget, Limit},
+    cache,
+    compiler::Compiler,
+    diagnostic::{self, Code, Diagnostic, ErrorFormat, Label, Severity},
+    environment::{Environment, Slot},
     expression::Expr,
-    native_functions::{ArrayWithLen, DeepClone, Len, NativeClock, ReadToString, ToNum, ToString},
+    functions::Function,
+    lint::{Linter, Lints},
+    memory::{self, MemoryStats, FIELD_SIZE},
+    native_functions::{
+        ArrayWithLen, Builder, ByteLen, Bytes, DeepClone, Exit, Gc, GcStats, Keys, Len,
+        MemoryStats as NativeMemoryStats, NativeClock, ReadToString, Slice, ToNum, ToString, Try,
+    },
+    optimizer::optimize,
     parser::Parser,
     raylib::{
         BeginDrawing, CheckCollisionCircleRec, CheckCollisionRecs, ClearBackground, DrawCircle,
         DrawFPS, DrawRectangle, DrawRectangleRec, DrawText, EndDrawing, GetFrameTime, InitWindow,
         IsKeyDown, KeyboardKey, SetTargetFPS, WindowShouldClose,
     },
+    resolver::Resolver,
     scanner::Scanner,
     statement::Stmt,
+    suggest,
+    symbol::Symbol,
     token::{Token, TokenKind},
-    value::{Object, Value},
+    value::{char_range, Fun, Object, StructMap, Value},
+    vm::Vm,
     IntError, WithToken,
 };
 
+/// Where a value was read from, used to write back strings since they can't be modified in place.
+enum Place {
+    Variable(Token, Slot),
+    Field(Value, Token),
+    Index(Value, Value, Token),
+}
+
+/// How running a source ended. The errors were already reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    /// The source had syntax errors or errors found before running it.
+    ParseError,
+    RuntimeError,
+    /// The program ran out of its budget or was interrupted.
+    Stopped(Limit),
+    /// The script called `exit(code)`.
+    Exit(i32),
+}
+
+/// A call that hasn't returned: the function and the token of the call.
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    pub fun: Fun,
+    pub call_site: Token,
+}
+
+// a traceback longer than this only shows its innermost calls
+const MAX_TRACEBACK: usize = 30;
+// how many times a call repeated on the same line is shown before it's only counted
+const SHOWN_REPEATS: usize = 2;
+
+/// How many calls can be active at once by default. Script calls recurse on the Rust stack, this
+/// many fit on an 8MB stack, the size of a main thread, even in a debug build.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 100;
+
+/// Which engine runs the programs, both behave the same.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Engine {
+    #[default]
+    TreeWalker,
+    Vm,
+}
+
 pub struct Interpreter {
-    environments: Vec<HashMap<String, Value>>,
+    pub(crate) globals: HashMap<Symbol, Value>,
+    environments: Vec<Vec<Value>>,
     environment: Environment,
     source: String,
+    engine: Engine,
+    optimize: bool,
+    call_stack: Vec<CallFrame>,
+    max_call_depth: usize,
+    pub(crate) budget: Budget,
+    memory_limit: Option<usize>,
+    // the script the source came from, errors name it
+    file: Option<String>,
+    error_format: ErrorFormat,
+    // the locals an undefined global could be a typo of, by where the global is used
+    suggestions: HashMap<usize, Vec<Symbol>>,
+    strict_fields: bool,
+    // the warnings reported so far
+    warnings: usize,
 }
 
 impl Default for Interpreter {
@@ -34,6 +118,16 @@ impl Default for Interpreter {
         globals.insert("str".into(), Value::new_fun(ToString));
         globals.insert("num".into(), Value::new_fun(ToNum));
         globals.insert("read_to_string".into(), Value::new_fun(ReadToString));
+        globals.insert("slice".into(), Value::new_fun(Slice));
+        globals.insert("byte_len".into(), Value::new_fun(ByteLen));
+        globals.insert("bytes".into(), Value::new_fun(Bytes));
+        globals.insert("builder".into(), Value::new_fun(Builder));
+        globals.insert("keys".into(), Value::new_fun(Keys));
+        globals.insert("gc".into(), Value::new_fun(Gc));
+        globals.insert("gc_stats".into(), Value::new_fun(GcStats));
+        globals.insert("memory_stats".into(), Value::new_fun(NativeMemoryStats));
+        globals.insert("try".into(), Value::new_fun(Try));
+        globals.insert("exit".into(), Value::new_fun(Exit));
         globals.insert("InitWindow".into(), Value::new_fun(InitWindow));
         globals.insert(
             "WindowShouldClose".into(),
@@ -76,118 +170,278 @@ impl Default for Interpreter {
             Value::Double(KeyboardKey::KEY_DOWN as u32 as f64),
         );
         Self {
-            environments: vec![globals],
-            environment: Environment::new(vec![0]),
+            globals,
+            environments: Vec::new(),
+            environment: Environment::default(),
             source: String::new(),
+            engine: Engine::default(),
+            optimize: false,
+            call_stack: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            budget: Budget::default(),
+            memory_limit: None,
+            file: None,
+            error_format: ErrorFormat::default(),
+            suggestions: HashMap::new(),
+            strict_fields: false,
+            warnings: 0,
         }
     }
 }
 
 impl Interpreter {
-    fn evalute(&mut self, expression: &Expr) -> Result<Value, IntError> {
-        match expression {
+    pub fn with_engine(engine: Engine) -> Self {
+        Self {
+            engine,
+            ..Self::default()
+        }
+    }
+
+    /// The file the next source is read from, errors show it.
+    pub fn set_file(&mut self, file: Option<String>) {
+        self.file = file;
+    }
+
+    pub fn set_error_format(&mut self, error_format: ErrorFormat) {
+        self.error_format = error_format;
+    }
+
+    /// Reading a field a struct doesn't have is an error naming the closest fields, instead of
+    /// `nil`.
+    pub fn set_strict_fields(&mut self, strict_fields: bool) {
+        self.strict_fields = strict_fields;
+    }
+
+    /// Runs the optimizer on every program before executing it.
+    pub fn set_optimize(&mut self, optimize: bool) {
+        self.optimize = optimize;
+    }
+
+    /// Calls nested deeper than this fail with "maximum recursion depth exceeded" instead of
+    /// overflowing the stack. Tail calls don't count. Every call takes up to 50KB of the Rust
+    /// stack in a debug build and a few KB in a release build, a deeper limit needs a thread with
+    /// a stack that big.
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    /// Stops every program after this many steps: statements in the tree walker, instructions
+    /// in the vm.
+    pub fn set_max_steps(&mut self, max_steps: Option<u64>) {
+        self.budget.max_steps = max_steps;
+    }
+
+    /// Stops every program that runs longer than `time_limit`.
+    pub fn set_time_limit(&mut self, time_limit: Option<Duration>) {
+        self.budget.time_limit = time_limit;
+    }
+
+    /// Setting the flag stops the running program, the interpreter keeps its globals and can run
+    /// the next one. It's meant to be set from another thread, e.g. a Ctrl-C handler.
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool> {
+        self.budget.interrupt_handle()
+    }
+
+    /// Limits the bytes used by strings, arrays and structs while this interpreter runs, going
+    /// over it is a runtime error. Objects are counted per thread, so the limit counts the
+    /// objects of other interpreters on the thread too.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory_limit = limit;
+    }
+
+    /// The live strings, arrays and structs on this thread and the bytes they use.
+    pub fn memory_stats(&self) -> MemoryStats {
+        memory::stats()
+    }
+
+    /// Fails if another call would go over the call depth limit, otherwise pushes its frame.
+    /// Every `enter_call` that succeeds is followed by an `exit_call`.
+    pub(crate) fn enter_call(&mut self, fun: &Fun, paren: &Token) -> Result<(), IntError> {
+        if self.call_stack.len() >= self.max_call_depth {
+            return Err(
+                IntError::new(Some(paren), "maximum recursion depth exceeded")
+                    .with_code(Code::RecursionDepth),
+            );
+        }
+        self.call_stack.push(CallFrame {
+            fun: fun.clone(),
+            call_site: paren.clone(),
+        });
+        Ok(())
+    }
+
+    pub(crate) fn exit_call(&mut self) {
+        self.call_stack.pop();
+    }
+
+    /// A tail call, the callee takes the frame of the function that returned.
+    pub(crate) fn replace_frame(&mut self, fun: &Fun) {
+        if let Some(frame) = self.call_stack.last_mut() {
+            frame.fun = fun.clone();
+        }
+    }
+
+    /// How many calls are active, the frames above it are dropped by `truncate_calls`.
+    pub(crate) fn call_depth(&self) -> usize {
+        self.call_stack.len()
+    }
+
+    pub(crate) fn truncate_calls(&mut self, depth: usize) {
+        self.call_stack.truncate(depth);
+    }
+
+    /// Records the active calls in an error that is leaving its innermost frame. An error without
+    /// a token, like one from a native function, points at the call.
+    pub(crate) fn trace(&self, mut err: IntError) -> IntError {
+        if let IntError::Error { token, trace, .. } = &mut err {
+            if trace.is_empty() {
+                trace.clone_from(&self.call_stack);
+            }
+            if token.is_none() {
+                *token = self.call_stack.last().map(|frame| frame.call_site.clone());
+            }
+        }
+        err
+    }
+
+    fn call(&mut self, fun: Fun, arguments: Vec<Value>, paren: &Token) -> Result<Value, IntError> {
+        self.enter_call(&fun, paren)?;
+        let result = fun.0.call(self, arguments).map_err(|err| self.trace(err));
+        self.exit_call();
+        result
+    }
+
+    /// Calls `fun` from a native function, the call happens where the native was called.
+    pub(crate) fn call_from_native(
+        &mut self,
+        fun: &Fun,
+        arguments: Vec<Value>,
+    ) -> Result<Value, IntError> {
+        let paren = self
+            .call_stack
+            .last()
+            .expect("natives are called through `call`")
+            .call_site
+            .clone();
+        if fun.0.arity() != arguments.len() {
+            return Err(arity_error(fun, arguments.len(), &paren));
+        }
+        self.call(fun.clone(), arguments, &paren)
+    }
+
+    /// Where an error happened and the calls that led there, innermost first: the function and
+    /// the line in it.
+    pub(crate) fn traceback(
+        &self,
+        token: Option<&Token>,
+        trace: &[CallFrame],
+    ) -> Vec<(String, Option<usize>)> {
+        let line = |token: Option<&Token>| {
+            token.map(|token| diagnostic::line_column(&self.source, token.span.start).0)
+        };
+        let mut traceback = Vec::with_capacity(trace.len() + 1);
+        let mut location = token;
+        for frame in trace.iter().rev() {
+            traceback.push((frame.fun.0.trace_name(), line(location)));
+            location = Some(&frame.call_site);
+        }
+        traceback.push(("<script>".into(), line(location)));
+        traceback
+    }
+
+    // "in f at line 3", then "called from g at line 7" for every caller. A call repeated on the
+    // same line, like a recursion, is shown a few times and then counted
+    fn traceback_notes(&self, token: Option<&Token>, trace: &[CallFrame]) -> Vec<String> {
+        if trace.is_empty() {
+            return Vec::new();
+        }
+        let mut notes = Vec::new();
+        let mut entries = self.traceback(token, trace).into_iter().peekable();
+        while let Some(entry) = entries.next() {
+            if notes.len() >= MAX_TRACEBACK {
+                notes.push(format!("... and {} more calls", entries.count() + 1));
+                break;
+            }
+            let mut repeats = 0;
+            while entries.next_if_eq(&entry).is_some() {
+                repeats += 1;
+            }
+            let (name, line) = entry;
+            let at = line.map_or(String::new(), |line| format!(" at line {line}"));
+            if notes.is_empty() {
+                notes.push(format!("in {name}{at}"));
+            } else {
+                notes.push(format!("called from {name}{at}"));
+            }
+            for _ in 0..repeats.min(SHOWN_REPEATS) {
+                notes.push(format!("called from {name}{at}"));
+            }
+            if repeats > SHOWN_REPEATS {
+                notes.push(format!(
+                    "... repeated {} more times",
+                    repeats - SHOWN_REPEATS
+                ));
+            }
+        }
+        notes
+    }
+
+    fn evalute(&mut self, ast: &Rc<Ast>, id: ExprId) -> Result<Value, IntError> {
+        match &ast[id] {
             Expr::Unary { operator, right } => {
-                let right = self.evalute(right)?;
-                match operator.kind {
-                    TokenKind::Minus => {
-                        let value = right.double().with_token(operator)?;
-                        Ok(Value::Double(-value))
-                    }
-                    TokenKind::Bang => Ok(Value::Bool(!right.is_truthy())),
-                    _ => unreachable!("Invalid unary operator: {operator:?}"),
-                }
+                let right = self.evalute(ast, *right)?;
+                unary(operator, right)
             }
             Expr::Binary {
                 left,
                 operator,
                 right,
             } => {
-                let left = self.evalute(left)?;
-                let right = self.evalute(right)?;
-                match operator.kind {
-                    TokenKind::Minus => Ok(Value::Double(
-                        left.double().with_token(operator)?
-                            - right.double().with_token(operator)?,
-                    )),
-                    TokenKind::Slash => Ok(Value::Double(
-                        left.double().with_token(operator)?
-                            / right.double().with_token(operator)?,
-                    )),
-                    TokenKind::Star => Ok(Value::Double(
-                        left.double().with_token(operator)?
-                            * right.double().with_token(operator)?,
-                    )),
-                    TokenKind::Plus => match (left, right) {
-                        (
-                            Value::Object(Object::String(left)),
-                            Value::Object(Object::String(right)),
-                        ) => Ok(Value::new_string(
-                            left.borrow().clone() + right.borrow().as_ref(),
-                        )),
-                        (Value::Object(Object::String(left)), Value::Double(right)) => Ok(
-                            Value::new_string(left.borrow().clone() + &right.to_string()),
-                        ),
-                        (Value::Double(left), Value::Object(Object::String(right))) => Ok(
-                            Value::new_string(left.to_string() + right.borrow().as_ref()),
-                        ),
-                        (Value::Double(left), Value::Double(right)) => {
-                            Ok(Value::Double(left + right))
-                        }
-                        _ => Err(IntError::Error {
-                            message: "One of the operands must be a string and a double".into(),
-                            token: Some(operator.as_ref().clone()),
-                        }),
-                    },
-                    TokenKind::BangEqual => Ok(Value::Bool(left.ne(&right))),
-                    TokenKind::EqualEqual => Ok(Value::Bool(left.eq(&right))),
-                    TokenKind::Greater => Ok(Value::Bool(
-                        left.double().with_token(operator)?
-                            > right.double().with_token(operator)?,
-                    )),
-                    TokenKind::GreaterEqual => Ok(Value::Bool(
-                        left.double().with_token(operator)?
-                            >= right.double().with_token(operator)?,
-                    )),
-                    TokenKind::Less => Ok(Value::Bool(
-                        left.double().with_token(operator)?
-                            < right.double().with_token(operator)?,
-                    )),
-                    TokenKind::LessEqual => Ok(Value::Bool(
-                        left.double().with_token(operator)?
-                            <= right.double().with_token(operator)?,
-                    )),
-                    TokenKind::Comma => Ok(right),
-                    _ => unreachable!("Invalid binary operator: {operator:?}"),
-                }
+                let left = self.evalute(ast, *left)?;
+                let right = self.evalute(ast, *right)?;
+                binary(operator, left, right)
             }
-            Expr::Grouping { expression } => self.evalute(expression),
-            Expr::Literal { value } => Ok(value.as_ref().clone()),
-            Expr::Variable { name } => {
-                let lexeme = self.lexeme(&name);
-                self.environment
-                    .get(lexeme, &self.environments)
-                    .ok_or(IntError::Error {
-                        message: format!("Undefined variable `{}`.", self.lexeme(&name)),
-                        token: Some(name.as_ref().clone()),
-                    })
-            }
-            Expr::Assign { name, expression } => {
-                let value = self.evalute(expression)?;
-                // HACK: fucking borrow checker
-                let lexeme = self.lexeme(&name).to_string();
+            Expr::Grouping { expression } => self.evalute(ast, *expression),
+            Expr::Literal { value } => Ok(value.clone()),
+            Expr::Variable { name, slot } => self
+                .environment
+                .get(name.name(), *slot, &self.globals, &self.environments)
+                .ok_or_else(|| self.undefined_variable(name)),
+            Expr::Assign {
+                name,
+                expression,
+                slot,
+            } => {
+                if let Some((left, operator, right)) = self_add(ast, name, *slot, *expression) {
+                    let left = self.evalute(ast, left)?;
+                    let right = self.evalute(ast, right)?;
+                    let Some(variable) = self.environment.get_mut(
+                        name.name(),
+                        *slot,
+                        &mut self.globals,
+                        &mut self.environments,
+                    ) else {
+                        return Err(self.undefined_variable(name));
+                    };
+                    return add_to_variable(variable, left, right, operator);
+                }
+                let value = self.evalute(ast, *expression)?;
                 self.environment
-                    .assign(&lexeme, value, &mut self.environments)
-                    .ok_or(IntError::Error {
-                        message: format!("Undefined variable `{}`.", self.lexeme(&name)),
-                        token: Some(name.as_ref().clone()),
-                    })
+                    .assign(
+                        name.name(),
+                        *slot,
+                        value,
+                        &mut self.globals,
+                        &mut self.environments,
+                    )
+                    .ok_or_else(|| self.undefined_variable(name))
             }
             Expr::Logical {
                 left,
                 operator,
                 right,
             } => {
-                let left = self.evalute(left)?;
+                let left = self.evalute(ast, *left)?;
                 match operator.kind {
                     TokenKind::And => {
                         if !left.is_truthy() {
@@ -202,74 +456,60 @@ impl Interpreter {
                     _ => unreachable!("Invalid logical operator: {operator:?}"),
                 }
 
-                self.evalute(right)
+                self.evalute(ast, *right)
             }
             Expr::Call {
                 callee,
                 paren,
                 arguments,
             } => {
-                let callee = self.evalute(callee)?;
-                let arguments = arguments
-                    .iter()
-                    .map(|arg| self.evalute(arg))
-                    .collect::<Result<Vec<_>, _>>()?;
-
-                let fun = callee.get_fun().with_token(paren)?;
-                if fun.0.arity() != arguments.len() {
-                    return Err(IntError::Error {
-                        message: format!(
-                            "Expected {} arguments, got {}",
-                            fun.0.arity(),
-                            arguments.len()
-                        ),
-                        token: Some((paren.as_ref()).clone()),
-                    });
-                }
-                fun.0.call(self, arguments)
+                let (fun, arguments) = self.evaluate_call(ast, *callee, paren, arguments)?;
+                self.call(fun, arguments, paren)
             }
             Expr::Ternary {
                 condition,
                 then_branch,
                 else_branch,
             } => {
-                if self.evalute(condition)?.is_truthy() {
-                    Ok(self.evalute(then_branch)?)
+                if self.evalute(ast, *condition)?.is_truthy() {
+                    Ok(self.evalute(ast, *then_branch)?)
                 } else {
-                    Ok(self.evalute(else_branch)?)
+                    Ok(self.evalute(ast, *else_branch)?)
                 }
             }
             Expr::Struct { fields } => {
-                let mut map = HashMap::new();
-                for (token, expr) in fields.as_ref() {
-                    let value = self.evalute(expr)?;
-                    map.insert(self.lexeme(&token).to_string(), value);
+                let mut map = StructMap::default();
+                for (token, expr) in fields {
+                    let value = self.evalute(ast, *expr)?;
+                    map.insert(token.name(), value);
                 }
                 Ok(Value::new_struct(map))
             }
             Expr::StructGet { target, name } => {
-                let value = self.evalute(target)?;
-                let map = value.get_struct().with_token(name)?;
-                let map = map.borrow();
-                let value = map.get(self.lexeme(name)).unwrap_or(&Value::Nil);
-                Ok(value.clone())
+                let target = self.evalute(ast, *target)?;
+                self.struct_get(&target, name)
             }
             Expr::StructSet {
                 target,
                 name,
                 value,
             } => {
-                let target = self.evalute(target)?;
+                let target = self.evalute(ast, *target)?;
                 let map = target.get_struct().with_token(name)?;
-                let value = self.evalute(value)?;
-                map.borrow_mut()
-                    .insert(self.lexeme(name).to_string(), value.clone());
+                let value = self.evalute(ast, *value)?;
+                if map
+                    .borrow_mut()
+                    .insert(name.name(), value.clone())
+                    .is_none()
+                {
+                    memory::grow(FIELD_SIZE).with_token(name)?;
+                }
                 Ok(value)
             }
             Expr::Array { elements } => {
                 let mut vec = Vec::new();
-                for element in elements.as_ref() {
-                    let value = self.evalute(element)?;
+                for element in elements {
+                    let value = self.evalute(ast, *element)?;
                     vec.push(value);
                 }
                 Ok(Value::new_array(vec))
@@ -278,119 +518,174 @@ impl Interpreter {
                 array,
                 bracket,
                 index,
-            } => match self.evalute(array)? {
-                Value::Object(Object::String(string)) => {
-                    let index = self.evalute(index)?.double().with_token(bracket)? as usize;
-                    let chars: Vec<_> = string.borrow().chars().collect();
-                    Ok(Value::new_string(
-                        chars
-                            .get(index)
-                            .ok_or(IntError::Error {
-                                message: format!(
-                                    "index `{index}` is out of bound `{size}`",
-                                    size = chars.len()
-                                ),
-                                token: Some(bracket.as_ref().clone()),
-                            })?
-                            .to_string(),
-                    ))
-                }
-                Value::Object(Object::Struct(map)) => {
-                    let key = self.evalute(index)?;
-                    let key = key.get_string().with_token(bracket)?.borrow();
-                    Ok(map
-                        .borrow()
-                        .get(key.as_str())
-                        .unwrap_or(&Value::Nil)
-                        .clone())
-                }
-                Value::Object(Object::Array(array)) => {
-                    let array = array.borrow();
-                    let index = self.evalute(index)?.double().with_token(bracket)? as usize;
-                    match array.get(index) {
-                        Some(value) => Ok(value.clone()),
-                        None => Err(IntError::Error {
-                            message: format!(
-                                "index `{index}` is out of bound `{len}`",
-                                len = array.len()
-                            ),
-                            token: Some(bracket.as_ref().clone()),
-                        }),
-                    }
-                }
-                _ => Err(IntError::Error {
-                    message: "Index operator can only be used on arrays, structs or strings".into(),
-                    token: Some(bracket.as_ref().clone()),
-                }),
-            },
+            } => {
+                let array = self.evalute(ast, *array)?;
+                let index = self.evalute(ast, *index)?;
+                index_get(&array, &index, bracket)
+            }
             Expr::IndexSet {
                 array,
                 bracket,
                 index,
                 value,
-            } => match self.evalute(array)? {
-                Value::Object(Object::Array(array)) => {
-                    let mut array = array.borrow_mut();
-                    let index = self.evalute(index)?.double().with_token(bracket)? as usize;
-                    let value = self.evalute(value)?;
-                    let Some(old_value) = array.get_mut(index) else {
-                        return Err(IntError::Error {
-                            message: format!(
-                                "index `{index}` is out of bound `{size}`",
-                                size = array.len()
-                            ),
-                            token: Some(bracket.as_ref().clone()),
-                        });
-                    };
-                    *old_value = value.clone();
-                    Ok(value)
-                }
-                Value::Object(Object::String(string)) => {
-                    let index = self.evalute(index)?.double().with_token(bracket)? as usize;
-                    let value = self.evalute(value)?;
-                    let str_value = value.get_string().with_token(bracket)?;
-                    // FIX: will crash if out of range
-                    string.borrow_mut().replace_range(
-                        index..(index + str_value.borrow().len()),
-                        str_value.borrow().as_str(),
-                    );
-                    Ok(value.clone())
+            } => {
+                let (array, place) = self.evaluate_place(ast, *array)?;
+                let index = self.evalute(ast, *index)?;
+                let value = self.evalute(ast, *value)?;
+                if let Some(string) = index_set(array, index, value.clone(), bracket)? {
+                    self.write_place(place, string, bracket)?;
                 }
-                Value::Object(Object::Struct(map)) => {
-                    let key = self.evalute(index)?;
-                    let value = self.evalute(value)?;
-                    let key = key.get_string().with_token(bracket)?.borrow();
-                    map.borrow_mut()
-                        .insert(key.as_str().to_string(), value.clone());
-                    Ok(value)
+                Ok(value)
+            }
+        }
+    }
+
+    // evaluates the callee and the arguments of a call and checks that they fit together
+    fn evaluate_call(
+        &mut self,
+        ast: &Rc<Ast>,
+        callee: ExprId,
+        paren: &Token,
+        arguments: &[ExprId],
+    ) -> Result<(Fun, Vec<Value>), IntError> {
+        let callee = self.evalute(ast, callee)?;
+        let arguments = arguments
+            .iter()
+            .map(|arg| self.evalute(ast, *arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let fun = callee.get_fun().with_token(paren)?;
+        if fun.0.arity() != arguments.len() {
+            return Err(arity_error(&fun, arguments.len(), paren));
+        }
+        Ok((fun, arguments))
+    }
+
+    fn struct_get(&self, target: &Value, name: &Token) -> Result<Value, IntError> {
+        let map = target.get_struct().with_token(name)?.borrow();
+        match map.get(name.name()) {
+            Some(value) => Ok(value.clone()),
+            None if self.strict_fields => Err(missing_field(&map, name)),
+            None => Ok(Value::Nil),
+        }
+    }
+
+    /// Whether reading a missing field is an error.
+    pub(crate) fn strict_fields(&self) -> bool {
+        self.strict_fields
+    }
+
+    /// The error for a global that isn't defined, with the globals and the locals that were in
+    /// scope that it's close to.
+    pub(crate) fn undefined_variable(&self, name: &Token) -> IntError {
+        let locals = self.suggestions.get(&name.span.start).into_iter().flatten();
+        let candidates = locals
+            .chain(self.globals.keys())
+            .map(|candidate| candidate.as_str());
+        let close = suggest::closest(name.name().as_str(), candidates);
+        IntError::new(
+            Some(name),
+            format!(
+                "Undefined variable `{}`.{}",
+                name.name(),
+                suggest::did_you_mean(&close)
+            ),
+        )
+        .with_code(Code::UndefinedVariable)
+    }
+
+    // evaluates `expression` and, when it's a variable, a struct field or an element, also returns
+    // where the value was read from, so that a string (which is a value) can be updated in place.
+    fn evaluate_place(
+        &mut self,
+        ast: &Rc<Ast>,
+        id: ExprId,
+    ) -> Result<(Value, Option<Place>), IntError> {
+        match &ast[id] {
+            Expr::Variable { name, slot } => {
+                let value = self.evalute(ast, id)?;
+                Ok((value, Some(Place::Variable(name.clone(), *slot))))
+            }
+            Expr::StructGet { target, name } => {
+                let target = self.evalute(ast, *target)?;
+                let value = self.struct_get(&target, name)?;
+                Ok((value, Some(Place::Field(target, name.clone()))))
+            }
+            Expr::IndexGet {
+                array,
+                bracket,
+                index,
+            } => {
+                let array = self.evalute(ast, *array)?;
+                let index = self.evalute(ast, *index)?;
+                let value = index_get(&array, &index, bracket)?;
+                Ok((value, Some(Place::Index(array, index, bracket.clone()))))
+            }
+            _ => Ok((self.evalute(ast, id)?, None)),
+        }
+    }
+
+    fn write_place(
+        &mut self,
+        place: Option<Place>,
+        value: Value,
+        token: &Token,
+    ) -> Result<(), IntError> {
+        match place {
+            Some(Place::Variable(name, slot)) => {
+                self.environment
+                    .assign(
+                        name.name(),
+                        slot,
+                        value,
+                        &mut self.globals,
+                        &mut self.environments,
+                    )
+                    .ok_or_else(|| self.undefined_variable(&name))?;
+                Ok(())
+            }
+            Some(Place::Field(target, name)) => {
+                let map = target.get_struct().with_token(&name)?;
+                map.borrow_mut().insert(name.name(), value);
+                Ok(())
+            }
+            Some(Place::Index(array, index, bracket)) => {
+                // only one level of places is tracked, a string inside a string can't be written back
+                match index_set(array, index, value, &bracket)? {
+                    Some(string) => self.write_place(None, string, &bracket),
+                    None => Ok(()),
                 }
-                _ => Err(IntError::Error {
-                    message: "Index operator can only be used on arrays, structs or strings".into(),
-                    token: Some(bracket.as_ref().clone()),
-                }),
-            },
+            }
+            None => Err(unwritable_string(token)),
         }
     }
 
-    fn execute(&mut self, statement: &Stmt) -> Result<(), IntError> {
-        match statement {
+    fn execute(&mut self, ast: &Rc<Ast>, id: StmtId) -> Result<(), IntError> {
+        self.budget.step()?;
+        match &ast[id] {
             Stmt::Print { expression } => {
-                let value = self.evalute(expression)?;
+                let value = self.evalute(ast, *expression)?;
                 println!("{value}");
                 Ok(())
             }
-            Stmt::Expression { expression } => self.evalute(expression).map(|_| {}),
-            Stmt::Var { name, initializer } => {
-                let value = self.evalute(initializer)?;
+            Stmt::Expression { expression } => self.evalute(ast, *expression).map(|_| {}),
+            Stmt::Var {
+                name,
+                initializer,
+                slot,
+            } => {
+                let value = self.evalute(ast, *initializer)?;
                 self.environment.define(
-                    self.lexeme(&name).to_string(),
+                    name.name(),
+                    *slot,
                     value,
+                    &mut self.globals,
                     &mut self.environments,
                 );
                 Ok(())
             }
             Stmt::Block { statements } => {
-                self.execute_block(statements, &self.environment.ids.clone(), HashMap::new())?;
+                self.execute_block(ast, statements, &self.environment.ids.clone(), Vec::new())?;
                 Ok(())
             }
             Stmt::If {
@@ -398,86 +693,99 @@ impl Interpreter {
                 then_branch,
                 else_branch,
             } => {
-                if self.evalute(condition)?.is_truthy() {
-                    self.execute(then_branch)?;
-                } else if let Some(else_branch) = else_branch.as_ref() {
-                    self.execute(else_branch)?;
+                if self.evalute(ast, *condition)?.is_truthy() {
+                    self.execute(ast, *then_branch)?;
+                } else if let Some(else_branch) = *else_branch {
+                    self.execute(ast, else_branch)?;
                 }
                 Ok(())
             }
             Stmt::While { condition, body } => {
-                while self.evalute(condition)?.is_truthy() {
-                    match self.execute(body) {
-                        Ok(()) => {}
+                while self.evalute(ast, *condition)?.is_truthy() {
+                    match self.execute(ast, *body) {
+                        Ok(()) | Err(IntError::Continue(_)) => {}
                         Err(IntError::Break(_)) => return Ok(()),
                         Err(e) => return Err(e),
                     }
                 }
                 Ok(())
             }
-            Stmt::Function { fun } => {
+            Stmt::Function { name, slot, .. } => {
+                // the function shares the body with the declaration, defining it copies nothing
                 self.environment.define(
-                    fun.name.clone(),
-                    Value::new_fun(fun.as_ref().clone()),
+                    name.name(),
+                    *slot,
+                    Value::new_fun(Function::new(ast.clone(), id)),
+                    &mut self.globals,
                     &mut self.environments,
                 );
                 Ok(())
             }
             Stmt::Return { keyword, value } => {
-                let return_value = self.evalute(value)?;
-                Err(IntError::ReturnValue(
-                    return_value,
-                    (keyword.as_ref()).clone(),
-                ))
+                // a call in tail position is left to the caller's `Function::call`, so tail
+                // recursion runs in constant stack space
+                if let Expr::Call {
+                    callee,
+                    paren,
+                    arguments,
+                } = &ast[*value]
+                {
+                    let (fun, arguments) = self.evaluate_call(ast, *callee, paren, arguments)?;
+                    if fun.0.as_function().is_some() {
+                        return Err(IntError::TailCall(fun, arguments));
+                    }
+                    let return_value = self.call(fun, arguments, paren)?;
+                    return Err(IntError::ReturnValue(return_value, keyword.clone()));
+                }
+                let return_value = self.evalute(ast, *value)?;
+                Err(IntError::ReturnValue(return_value, keyword.clone()))
             }
-            Stmt::Break { keyword } => Err(IntError::Break(keyword.as_ref().clone())),
+            Stmt::Break { keyword } => Err(IntError::Break(keyword.clone())),
             Stmt::For {
                 initializer,
                 condition,
                 increment,
                 body,
             } => {
-                if let Some(initializer) = initializer.as_ref() {
-                    self.execute(initializer)?;
+                if let Some(initializer) = *initializer {
+                    self.execute(ast, initializer)?;
                 }
-                while self.evalute(condition)?.is_truthy() {
-                    match self.execute(body) {
+                while self.evalute(ast, *condition)?.is_truthy() {
+                    match self.execute(ast, *body) {
                         Ok(()) | Err(IntError::Continue(_)) => {}
                         Err(IntError::Break(_)) => return Ok(()),
                         Err(err) => return Err(err),
                     }
 
-                    if let Some(increment) = increment.as_ref() {
-                        self.evalute(increment)?;
+                    if let Some(increment) = *increment {
+                        self.evalute(ast, increment)?;
                     }
                 }
                 Ok(())
             }
-            Stmt::Continue { keyword } => Err(IntError::Continue(keyword.as_ref().clone())),
+            Stmt::Continue { keyword } => Err(IntError::Continue(keyword.clone())),
             Stmt::Append {
                 paren,
                 array,
                 expression,
             } => {
-                let expression = self.evalute(expression)?;
-                match self.evalute(array)? {
-                    Value::Object(Object::Array(array)) => {
-                        array.borrow_mut().push(expression);
-                        Ok(())
-                    }
-                    Value::Object(Object::String(string)) => {
-                        string
-                            .borrow_mut()
-                            .push_str(expression.get_string()?.borrow().as_str());
-                        Ok(())
-                    }
-                    _ => Err(IntError::Error {
-                        message: "Invalid argument to append".into(),
-                        token: Some(paren.as_ref().clone()),
-                    }),
+                let expression = self.evalute(ast, *expression)?;
+                let (array, place) = self.evaluate_place(ast, *array)?;
+                if let Some(Place::Variable(name, slot)) = &place {
+                    let Some(variable) = self.environment.get_mut(
+                        name.name(),
+                        *slot,
+                        &mut self.globals,
+                        &mut self.environments,
+                    ) else {
+                        return Err(self.undefined_variable(name));
+                    };
+                    return append_to_variable(variable, array, expression, paren);
+                }
+                if let Some(string) = append(array, expression, paren)? {
+                    self.write_place(place, string, paren)?;
                 }
-                // let array = array.get_array().with_token(paren)?;
-                // array.borrow_mut().push(expression);
+                Ok(())
             }
             Stmt::Insert {
                 paren,
@@ -485,98 +793,226 @@ impl Interpreter {
                 index,
                 expression,
             } => {
-                let array = self.evalute(array)?;
-                let expression = self.evalute(expression)?;
-                let mut vec = array.get_array().with_token(paren)?.borrow_mut();
-                let index = self.evalute(index)?.double().with_token(paren)? as usize;
-                if index > vec.len() {
-                    return Err(IntError::Error {
-                        message: format!(
-                            "index `{index}` is out of bound `{size}`",
-                            size = vec.len()
-                        ),
-                        token: Some(paren.as_ref().clone()),
-                    });
-                }
-                vec.insert(index, expression);
-                Ok(())
+                let array = self.evalute(ast, *array)?;
+                let expression = self.evalute(ast, *expression)?;
+                let index = self.evalute(ast, *index)?;
+                insert(&array, index, expression, paren)
             }
             Stmt::Delete {
                 paren,
                 array,
                 index,
             } => {
-                let array = self.evalute(array)?;
-                let mut array = array.get_array().with_token(paren)?.borrow_mut();
-                let index = self.evalute(index)?.double().with_token(paren)? as usize;
-                if index >= array.len() {
-                    return Err(IntError::Error {
-                        message: format!(
-                            "index `{index}` is out of bound `{size}`",
-                            size = array.len()
-                        ),
-                        token: Some(paren.as_ref().clone()),
-                    });
-                }
-                array.remove(index);
-                Ok(())
+                let array = self.evalute(ast, *array)?;
+                let index = self.evalute(ast, *index)?;
+                delete(&array, index, paren)
             }
         }
     }
 
-    pub fn interpret(&mut self, source: String) {
-        let mut scanner = Scanner::new(source);
-        scanner.scan();
-        let mut parser = Parser::new(scanner);
-        parser.parse();
-        let statements = parser.statements;
-        self.source = parser.source;
+    /// Runs `source`, unless it has errors that can be found without running it.
+    pub fn interpret(&mut self, source: String) -> Outcome {
+        match self.parse(source) {
+            Some(program) => self.run(program),
+            None => Outcome::ParseError,
+        }
+    }
 
-        for statement in &statements {
-            match self.execute(statement) {
-                Ok(()) => {}
-                Err(IntError::ReturnValue(_, keyword)) => {
-                    println!(
-                        "Error interpreting: Top level return is not allowed. At line: {}",
-                        keyword.line
-                    );
-                    return;
-                }
-                Err(IntError::Error { message, token }) => {
-                    match token {
-                        Some(token) => println!(
-                            "Error interpreting `{}` at line {}: {}",
-                            self.lexeme(&token),
-                            token.line,
-                            message
-                        ),
-                        None => println!("Error interpreting `{message}`"),
-                    };
-                    return;
+    /// Like `interpret`, but the parsed program is read from the cache file `cache` if it was
+    /// made from the same source, and written to it otherwise.
+    pub fn interpret_cached(&mut self, source: String, cache: &Path) -> Outcome {
+        let start = self.source.len();
+        let cached = fs::read(cache)
+            .ok()
+            .and_then(|bytes| cache::decode(&bytes, &source, start));
+        let program = match cached {
+            Some(program) => {
+                self.source.push_str(&source);
+                for warning in &program.warnings {
+                    self.report(warning);
                 }
-                Err(IntError::Break(keyword)) => {
-                    println!(
-                        "Error interpreting: break is only allowed in loops. At line: {}",
-                        keyword.line
+                program
+            }
+            None => {
+                let Some(program) = self.parse(source) else {
+                    return Outcome::ParseError;
+                };
+                // without a cache the next run just parses again
+                let encoded = cache::encode(&program, &self.source[start..], start);
+                if let Err(err) = cache::write(cache, &encoded) {
+                    eprintln!(
+                        "WARNING: Couldn't write the cache {}: {err}",
+                        cache.display()
                     );
-                    return;
                 }
-                Err(IntError::Continue(keyword)) => {
-                    println!(
-                        "Error interpreting: continue is only allowed in loops. At line: {}",
-                        keyword.line
-                    );
-                    return;
+                program
+            }
+        };
+        self.run(program)
+    }
+
+    /// Parses `source` and writes the program to the cache file `cache` without running it.
+    /// Returns false if the program has errors, they were printed and nothing was written.
+    pub fn compile(&mut self, source: String, cache: &Path) -> io::Result<bool> {
+        let start = self.source.len();
+        match self.parse(source) {
+            Some(program) => {
+                cache::write(
+                    cache,
+                    &cache::encode(&program, &self.source[start..], start),
+                )?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Reports the errors and the warnings of the enabled `lints` in `source` without running
+    /// it. Returns the number of warnings, or None if it has errors.
+    pub fn check(&mut self, source: String, lints: &Lints) -> Option<usize> {
+        let warnings = self.warnings;
+        let program = self.parse(source)?;
+        let natives = self
+            .globals
+            .iter()
+            .filter_map(|(&name, value)| match value {
+                Value::Fun(fun) => Some((name, fun.0.arity())),
+                _ => None,
+            })
+            .collect();
+        let lints = Linter::new(&program.ast, lints, natives).lint(&program);
+        for warning in &lints {
+            self.report(warning);
+        }
+        Some(self.warnings - warnings)
+    }
+
+    // scans, parses and resolves `source` after the previous source. A source with errors leaves
+    // no program, every error is reported.
+    fn parse(&mut self, source: String) -> Option<Program> {
+        // functions defined by an earlier call (a previous line in the REPL) keep tokens that
+        // point into their source, so new source is appended to it instead of replacing it
+        let start = self.source.len();
+        let mut full_source = mem::take(&mut self.source);
+        full_source.push_str(&source);
+        let mut scanner = Scanner::starting_at(full_source, start);
+        scanner.scan();
+        let mut parser = Parser::new(scanner);
+        let statements = parser.parse();
+        let ast = parser.ast;
+        self.source = parser.source;
+        for warning in &parser.warnings {
+            self.report(warning);
+        }
+        let statements = match statements {
+            Ok(statements) => statements,
+            Err(diagnostics) => {
+                for diagnostic in &diagnostics {
+                    self.report(diagnostic);
                 }
+                return None;
             }
+        };
+
+        let mut program = Program {
+            ast,
+            statements,
+            warnings: parser.warnings,
+            ..Program::default()
+        };
+        let errors = Resolver::default().resolve(&mut program);
+        if !errors.is_empty() {
+            for error in &errors {
+                self.report(error);
+            }
+            return None;
+        }
+        Some(program)
+    }
+
+    /// Runs a program parsed from source that was already added to the interpreter's.
+    fn run(&mut self, program: Program) -> Outcome {
+        let Program {
+            mut ast,
+            mut statements,
+            suggestions,
+            ..
+        } = program;
+        self.suggestions.extend(
+            suggestions
+                .into_iter()
+                .map(|(name, locals)| (name.span.start, locals)),
+        );
+        if self.optimize {
+            optimize(&mut ast, &mut statements);
+        }
+
+        self.budget.start();
+        memory::set_limit(self.memory_limit);
+        let result = match self.engine {
+            Engine::TreeWalker => {
+                let ast = Rc::new(ast);
+                statements
+                    .iter()
+                    .try_for_each(|&statement| self.execute(&ast, statement))
+            }
+            Engine::Vm => {
+                let chunk = Compiler::new(&ast).compile(&statements);
+                Vm::new(self).run(Rc::new(chunk), Vec::new()).map(|_| ())
+            }
+        };
+        memory::set_limit(None);
+        let diagnostic = match result {
+            Ok(()) => return Outcome::Success,
+            Err(IntError::Exit(code)) => return Outcome::Exit(code),
+            Err(IntError::ReturnValue(_, keyword)) => {
+                Diagnostic::error(Code::TopLevelReturn, "Top level return is not allowed.")
+                    .at(Some(&keyword))
+            }
+            Err(IntError::Error {
+                code,
+                message,
+                token,
+                labels,
+                trace,
+            }) => Diagnostic::error(code, message)
+                .at(token.as_ref())
+                .with_labels(labels)
+                .with_notes(self.traceback_notes(token.as_ref(), &trace)),
+            Err(IntError::Stopped(limit)) => {
+                let diagnostic =
+                    Diagnostic::error(Code::Stopped, format!("Execution stopped: {limit}"));
+                self.report(&diagnostic);
+                return Outcome::Stopped(limit);
+            }
+            Err(IntError::TailCall(..)) => unreachable!("tail calls only happen in functions"),
+            Err(IntError::Break(keyword)) => {
+                Diagnostic::error(Code::BreakOutsideLoop, "break is only allowed in loops.")
+                    .at(Some(&keyword))
+            }
+            Err(IntError::Continue(keyword)) => Diagnostic::error(
+                Code::ContinueOutsideLoop,
+                "continue is only allowed in loops.",
+            )
+            .at(Some(&keyword)),
+        };
+        self.report(&diagnostic);
+        Outcome::RuntimeError
+    }
+
+    fn report(&mut self, diagnostic: &Diagnostic) {
+        if diagnostic.severity == Severity::Warning {
+            self.warnings += 1;
         }
+        diagnostic.emit(&self.source, self.file.as_deref(), self.error_format);
     }
 
     pub fn execute_block(
         &mut self,
-        statements: &[Stmt],
+        ast: &Rc<Ast>,
+        statements: &[StmtId],
         enclosing_ids: &[usize],
-        values: HashMap<String, Value>,
+        values: Vec<Value>,
     ) -> Result<(), IntError> {
         self.environments.push(values);
         let mut environment = Environment::new(
@@ -588,8 +1024,8 @@ impl Interpreter {
         );
         mem::swap(&mut environment, &mut self.environment);
         let mut result = Ok(());
-        for statement in statements {
-            match self.execute(statement) {
+        for &statement in statements {
+            match self.execute(ast, statement) {
                 Ok(()) => {}
                 Err(err) => {
                     result = Err(err);
@@ -607,3 +1043,375 @@ impl Interpreter {
         &self.source[token.span.start..token.span.end]
     }
 }
+
+// The operations below are shared by the tree walker and the vm, so both engines behave the same.
+
+pub(crate) fn unary(operator: &Token, right: Value) -> Result<Value, IntError> {
+    match operator.kind {
+        TokenKind::Minus => {
+            let value = right.double().with_token(operator)?;
+            Ok(Value::Double(-value))
+        }
+        TokenKind::Bang => Ok(Value::Bool(!right.is_truthy())),
+        _ => unreachable!("Invalid unary operator: {operator:?}"),
+    }
+}
+
+pub(crate) fn binary(operator: &Token, left: Value, right: Value) -> Result<Value, IntError> {
+    match operator.kind {
+        TokenKind::Minus => Ok(Value::Double(
+            left.double().with_token(operator)? - right.double().with_token(operator)?,
+        )),
+        TokenKind::Slash => Ok(Value::Double(
+            left.double().with_token(operator)? / right.double().with_token(operator)?,
+        )),
+        TokenKind::Star => Ok(Value::Double(
+            left.double().with_token(operator)? * right.double().with_token(operator)?,
+        )),
+        TokenKind::Plus => match (left, right) {
+            (Value::Object(Object::String(left)), Value::Object(Object::String(right))) => {
+                memory::reserve(left.len() + right.len()).with_token(operator)?;
+                // the left operand is only copied if something else still refers to it
+                let mut left = Rc::unwrap_or_clone(left);
+                left.push_str(&right);
+                Ok(Value::new_string(left))
+            }
+            (Value::Object(Object::String(left)), Value::Double(right)) => {
+                let mut left = Rc::unwrap_or_clone(left);
+                left.push_str(&right.to_string());
+                Ok(Value::new_string(left))
+            }
+            (Value::Double(left), Value::Object(Object::String(right))) => {
+                Ok(Value::new_string(left.to_string() + right.as_str()))
+            }
+            (Value::Double(left), Value::Double(right)) => Ok(Value::Double(left + right)),
+            _ => Err(IntError::new(
+                Some(operator),
+                "One of the operands must be a string and a double",
+            )
+            .with_code(Code::TypeMismatch)),
+        },
+        TokenKind::BangEqual => Ok(Value::Bool(left.ne(&right))),
+        TokenKind::EqualEqual => Ok(Value::Bool(left.eq(&right))),
+        TokenKind::Is => Ok(Value::Bool(left.is(&right))),
+        TokenKind::IsNot => Ok(Value::Bool(!left.is(&right))),
+        TokenKind::Greater => Ok(Value::Bool(
+            left.double().with_token(operator)? > right.double().with_token(operator)?,
+        )),
+        TokenKind::GreaterEqual => Ok(Value::Bool(
+            left.double().with_token(operator)? >= right.double().with_token(operator)?,
+        )),
+        TokenKind::Less => Ok(Value::Bool(
+            left.double().with_token(operator)? < right.double().with_token(operator)?,
+        )),
+        TokenKind::LessEqual => Ok(Value::Bool(
+            left.double().with_token(operator)? <= right.double().with_token(operator)?,
+        )),
+        TokenKind::Comma => Ok(right),
+        _ => unreachable!("Invalid binary operator: {operator:?}"),
+    }
+}
+
+pub(crate) fn unwritable_string(token: &Token) -> IntError {
+    IntError::new(Some(token), "Strings are values, only a string stored in a variable, a field or an element can be modified")
+}
+
+pub(crate) fn arity_error(fun: &Fun, count: usize, paren: &Token) -> IntError {
+    let defined = fun
+        .0
+        .declared_at()
+        .map(|span| Label::new(span, "function defined here"));
+    IntError::new(
+        Some(paren),
+        format!("Expected {} arguments, got {count}", fun.0.arity()),
+    )
+    .with_code(Code::Arity)
+    .with_labels(defined.into_iter().collect())
+}
+
+// with strict fields a struct doesn't read as `nil` where it has no field
+pub(crate) fn missing_field(map: &StructMap, name: &Token) -> IntError {
+    let fields = map.keys().map(|field| field.as_str());
+    let close = suggest::closest(name.name().as_str(), fields);
+    let hint = if !close.is_empty() {
+        suggest::did_you_mean(&close)
+    } else if map.is_empty() {
+        " It has no fields.".to_string()
+    } else {
+        let fields: Vec<String> = map.keys().map(|field| format!("`{field}`")).collect();
+        format!(" Its fields are {}.", fields.join(", "))
+    };
+    IntError::new(
+        Some(name),
+        format!("Struct has no field `{}`.{hint}", name.name()),
+    )
+    .with_code(Code::MissingField)
+}
+
+pub(crate) fn index_get(array: &Value, index: &Value, bracket: &Token) -> Result<Value, IntError> {
+    match array {
+        Value::Object(Object::String(string)) => {
+            let index = index.double().with_token(bracket)? as usize;
+            match string.chars().nth(index) {
+                Some(char) => Ok(Value::new_string(char.to_string())),
+                None => Err(IntError::new(
+                    Some(bracket),
+                    format!(
+                        "index `{index}` is out of bound `{size}`",
+                        size = string.chars().count()
+                    ),
+                )
+                .with_code(Code::IndexOutOfBounds)),
+            }
+        }
+        Value::Object(Object::Struct(map)) => {
+            let key = index.get_string().with_token(bracket)?;
+            let value = map.borrow().get_name(key).cloned();
+            Ok(value.unwrap_or(Value::Nil))
+        }
+        Value::Object(Object::Array(array)) => {
+            let array = array.borrow();
+            let index = index.double().with_token(bracket)? as usize;
+            match array.get(index) {
+                Some(value) => Ok(value.clone()),
+                None => Err(IntError::new(
+                    Some(bracket),
+                    format!("index `{index}` is out of bound `{len}`", len = array.len()),
+                )
+                .with_code(Code::IndexOutOfBounds)),
+            }
+        }
+        _ => Err(IntError::new(
+            Some(bracket),
+            "Index operator can only be used on arrays, structs or strings",
+        )
+        .with_code(Code::TypeMismatch)),
+    }
+}
+
+/// Arrays and structs are modified in place, for a string the modified copy is returned and the
+/// caller has to write it back to where the string was read from.
+pub(crate) fn index_set(
+    array: Value,
+    index: Value,
+    value: Value,
+    bracket: &Token,
+) -> Result<Option<Value>, IntError> {
+    match array {
+        Value::Object(Object::Array(array)) => {
+            let mut array = array.borrow_mut();
+            let index = index.double().with_token(bracket)? as usize;
+            let size = array.len();
+            let Some(old_value) = array.get_mut(index) else {
+                return Err(IntError::new(
+                    Some(bracket),
+                    format!("index `{index}` is out of bound `{size}`"),
+                )
+                .with_code(Code::IndexOutOfBounds));
+            };
+            *old_value = value;
+            Ok(None)
+        }
+        Value::Object(Object::String(string)) => {
+            let index = index.double().with_token(bracket)? as usize;
+            let replacement = value.get_string().with_token(bracket)?;
+            let Some(range) = char_range(&string, index, index + replacement.chars().count())
+            else {
+                return Err(IntError::new(
+                    Some(bracket),
+                    format!(
+                        "index `{index}` is out of bound `{size}`",
+                        size = string.chars().count()
+                    ),
+                )
+                .with_code(Code::IndexOutOfBounds));
+            };
+            let mut string = Rc::unwrap_or_clone(string);
+            string.replace_range(range, replacement);
+            Ok(Some(Value::new_string(string)))
+        }
+        Value::Object(Object::Struct(map)) => {
+            let key = index.get_string().with_token(bracket)?;
+            let mut map = map.borrow_mut();
+            let name_bytes = map.name_bytes();
+            if map.insert_name(key, value).is_none() {
+                let size = FIELD_SIZE + map.name_bytes() - name_bytes;
+                drop(map);
+                memory::grow(size).with_token(bracket)?;
+            }
+            Ok(None)
+        }
+        _ => Err(IntError::new(
+            Some(bracket),
+            "Index operator can only be used on arrays, structs or strings",
+        )
+        .with_code(Code::TypeMismatch)),
+    }
+}
+
+/// Like `index_set`, returns the appended string when `array` is a string.
+pub(crate) fn append(
+    array: Value,
+    expression: Value,
+    paren: &Token,
+) -> Result<Option<Value>, IntError> {
+    match array {
+        Value::Object(Object::Array(array)) => {
+            memory::grow(size_of::<Value>()).with_token(paren)?;
+            array.borrow_mut().push(expression);
+            Ok(None)
+        }
+        Value::Object(Object::Builder(builder)) => {
+            let expression = expression.get_string().with_token(paren)?;
+            memory::grow(expression.len()).with_token(paren)?;
+            builder.borrow_mut().push_str(expression);
+            Ok(None)
+        }
+        Value::Object(Object::String(string)) => {
+            let expression = expression.get_string().with_token(paren)?;
+            memory::reserve(string.len() + expression.len()).with_token(paren)?;
+            let mut string = Rc::unwrap_or_clone(string);
+            string.push_str(expression);
+            Ok(Some(Value::new_string(string)))
+        }
+        _ => {
+            Err(IntError::new(Some(paren), "Invalid argument to append")
+                .with_code(Code::TypeMismatch))
+        }
+    }
+}
+
+/// The operands of `name = name + right`, which appends to a string in `name` in place.
+pub(crate) fn self_add<'a>(
+    ast: &'a Ast,
+    name: &Token,
+    slot: Slot,
+    expression: ExprId,
+) -> Option<(ExprId, &'a Token, ExprId)> {
+    let Expr::Binary {
+        left,
+        operator,
+        right,
+    } = &ast[expression]
+    else {
+        return None;
+    };
+    match &ast[*left] {
+        Expr::Variable {
+            name: left_name,
+            slot: left_slot,
+        } if operator.kind == TokenKind::Plus
+            && left_name.name() == name.name()
+            && *left_slot == slot =>
+        {
+            Some((*left, operator, *right))
+        }
+        _ => None,
+    }
+}
+
+/// `variable = left + right` where `left` was read from `variable`.
+pub(crate) fn add_to_variable(
+    variable: &mut Value,
+    left: Value,
+    right: Value,
+    operator: &Token,
+) -> Result<Value, IntError> {
+    let suffix = match &right {
+        Value::Object(Object::String(right)) => Cow::Borrowed(right.as_str()),
+        Value::Double(right) => Cow::Owned(right.to_string()),
+        _ => Cow::Borrowed(""),
+    };
+    let appends = matches!(right, Value::Object(Object::String(_)) | Value::Double(_));
+    if appends && holds(variable, &left) {
+        drop(left);
+        push_to_variable(variable, &suffix, operator)?;
+        return Ok(variable.clone());
+    }
+    drop(suffix);
+    let value = binary(operator, left, right)?;
+    *variable = value.clone();
+    Ok(value)
+}
+
+/// `append(variable, expression)` where `array` was read from `variable`.
+pub(crate) fn append_to_variable(
+    variable: &mut Value,
+    array: Value,
+    expression: Value,
+    paren: &Token,
+) -> Result<(), IntError> {
+    if holds(variable, &array) {
+        drop(array);
+        let expression = expression.get_string().with_token(paren)?;
+        return push_to_variable(variable, expression, paren);
+    }
+    if let Some(string) = append(array, expression, paren)? {
+        *variable = string;
+    }
+    Ok(())
+}
+
+// whether `variable` still holds the string `value` that was read from it. Once `value` is
+// dropped the variable may have the only reference, then its string can grow in place
+fn holds(variable: &Value, value: &Value) -> bool {
+    match (variable, value) {
+        (Value::Object(Object::String(variable)), Value::Object(Object::String(value))) => {
+            Rc::ptr_eq(variable, value)
+        }
+        _ => false,
+    }
+}
+
+// appends to the string in `variable`, copying it only if something else refers to it too
+fn push_to_variable(variable: &mut Value, suffix: &str, token: &Token) -> Result<(), IntError> {
+    let Value::Object(Object::String(string)) = mem::replace(variable, Value::Nil) else {
+        unreachable!("only called on variables holding a string");
+    };
+    if let Err(err) = memory::reserve(string.len() + suffix.len()) {
+        *variable = Value::Object(Object::String(string));
+        return Err(err).with_token(token);
+    }
+    // the memory tracker's weak reference doesn't count, the string is moved out
+    let mut string = Rc::try_unwrap(string).unwrap_or_else(|string| string.to_string());
+    string.push_str(suffix);
+    *variable = Value::new_string(string);
+    Ok(())
+}
+
+pub(crate) fn insert(
+    array: &Value,
+    index: Value,
+    expression: Value,
+    paren: &Token,
+) -> Result<(), IntError> {
+    let mut vec = array.get_array().with_token(paren)?.borrow_mut();
+    let index = index.double().with_token(paren)? as usize;
+    if index > vec.len() {
+        return Err(IntError::new(
+            Some(paren),
+            format!("index `{index}` is out of bound `{size}`", size = vec.len()),
+        )
+        .with_code(Code::IndexOutOfBounds));
+    }
+    memory::grow(size_of::<Value>()).with_token(paren)?;
+    vec.insert(index, expression);
+    Ok(())
+}
+
+pub(crate) fn delete(array: &Value, index: Value, paren: &Token) -> Result<(), IntError> {
+    let mut array = array.get_array().with_token(paren)?.borrow_mut();
+    let index = index.double().with_token(paren)? as usize;
+    if index >= array.len() {
+        return Err(IntError::new(
+            Some(paren),
+            format!(
+                "index `{index}` is out of bound `{size}`",
+                size = array.len()
+            ),
+        )
+        .with_code(Code::IndexOutOfBounds));
+    }
+    array.remove(index);
+    Ok(())
+}
@@ -1,16 +1,30 @@
 use token::Token;
 
+pub mod ast;
+pub mod budget;
+pub mod cache;
+pub mod chunk;
+pub mod compiler;
+pub mod diagnostic;
 pub mod environment;
 pub mod expression;
 pub mod functions;
+pub mod gc;
 pub mod interpreter;
+pub mod lint;
+pub mod memory;
 pub mod native_functions;
+pub mod optimizer;
 pub mod parser;
 pub mod raylib;
+pub mod resolver;
 pub mod scanner;
 pub mod statement;
+pub mod suggest;
+pub mod symbol;
 pub mod token;
 pub mod value;
+pub mod vm;
 
 macro_rules! generate_enum_and_functions {
     ($enum_name:ident {
@@ -18,50 +32,101 @@ macro_rules! generate_enum_and_functions {
     ) => {
         #[derive(Debug, Clone)]
         pub enum $enum_name {
-            // TODO: remove this box vvv
-            $( $variant { $( $field: Box<$field_type> ),* } ),*
+            $( $variant { $( $field: $field_type ),* } ),*
         }
             $(
                 pub fn $variant($( $field: $field_type ),*) -> $enum_name {
                     $enum_name::$variant {
-                    $($field: $field.into()),*
+                    $($field),*
                 }
                 }
             )*
     };
 }
 
+use budget::Limit;
+use diagnostic::{Code, Label};
 pub(crate) use generate_enum_and_functions;
-use value::Value;
+use interpreter::CallFrame;
+use value::{Fun, Value};
 
 pub enum IntError {
     Error {
+        code: Code,
         message: String,
         token: Option<Token>,
+        /// Other places that explain the error, like where a function was defined.
+        labels: Vec<Label>,
+        /// The calls that were active when the error happened, outermost first.
+        trace: Vec<CallFrame>,
     },
     ReturnValue(Value, Token),
+    /// A `return` of a call, the call is made by `Function::call` once the returning function's
+    /// frame is gone.
+    TailCall(Fun, Vec<Value>),
     Break(Token),
     Continue(Token),
+    /// The program ran out of its `Budget` or was interrupted. `try` catches it, but a spent
+    /// budget stops the program again at its next step.
+    Stopped(Limit),
+    /// The script called `exit(code)`, it ends the program like `Stopped`.
+    Exit(i32),
+}
+
+impl IntError {
+    /// A runtime error at `token`, other kinds of errors set their code with `with_code`. Where
+    /// it was raised from and other places that explain it are added with `with_trace` and
+    /// `with_labels`.
+    pub fn new(token: Option<&Token>, message: impl Into<String>) -> Self {
+        IntError::Error {
+            code: Code::Runtime,
+            message: message.into(),
+            token: token.cloned(),
+            labels: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn with_code(mut self, new_code: Code) -> Self {
+        if let IntError::Error { code, .. } = &mut self {
+            *code = new_code;
+        }
+        self
+    }
+
+    pub fn with_labels(mut self, new_labels: Vec<Label>) -> Self {
+        if let IntError::Error { labels, .. } = &mut self {
+            *labels = new_labels;
+        }
+        self
+    }
+
+    pub fn with_trace(mut self, new_trace: Vec<CallFrame>) -> Self {
+        if let IntError::Error { trace, .. } = &mut self {
+            *trace = new_trace;
+        }
+        self
+    }
 }
 
 trait WithToken<T> {
     fn with_token(self, token: impl AsRef<Token>) -> Result<T, IntError>;
 }
 
-impl<T> WithToken<T> for Result<T, String> {
+impl<T, E: Into<IntError>> WithToken<T> for Result<T, E> {
     fn with_token(self, token: impl AsRef<Token>) -> Result<T, IntError> {
-        self.map_err(|msg| IntError::Error {
-            message: msg,
-            token: Some(token.as_ref().clone()),
+        self.map_err(|err| {
+            let mut err = err.into();
+            if let IntError::Error { token: at, .. } = &mut err {
+                *at = Some(token.as_ref().clone());
+            }
+            err
         })
     }
 }
 
 impl From<String> for IntError {
     fn from(value: String) -> Self {
-        IntError::Error {
-            message: value,
-            token: None,
-        }
+        IntError::new(None, value)
     }
 }
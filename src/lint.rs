@@ -0,0 +1,598 @@
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+use std::mem;
+
+use crate::{
+    ast::{Ast, ExprId, Program, StmtId},
+    diagnostic::{Code, Diagnostic, Label},
+    expression::Expr,
+    statement::Stmt,
+    suggest,
+    symbol::Symbol,
+    token::{Span, Token},
+};
+
+/// A kind of warning `int check` reports. Each one can be turned off with `--allow=NAME`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    UnusedVariable,
+    UnusedParameter,
+    Redeclaration,
+    ShadowedNative,
+    Unreachable,
+    UndeclaredAssignment,
+    ArgumentCount,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 7] = [
+        Lint::UnusedVariable,
+        Lint::UnusedParameter,
+        Lint::Redeclaration,
+        Lint::ShadowedNative,
+        Lint::Unreachable,
+        Lint::UndeclaredAssignment,
+        Lint::ArgumentCount,
+    ];
+
+    /// The name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnusedParameter => "unused-parameter",
+            Lint::Redeclaration => "redeclaration",
+            Lint::ShadowedNative => "shadowed-native",
+            Lint::Unreachable => "unreachable",
+            Lint::UndeclaredAssignment => "undeclared-assignment",
+            Lint::ArgumentCount => "argument-count",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+
+    fn code(self) -> Code {
+        match self {
+            Lint::UnusedVariable => Code::UnusedVariable,
+            Lint::UnusedParameter => Code::UnusedParameter,
+            Lint::Redeclaration => Code::Redeclaration,
+            Lint::ShadowedNative => Code::ShadowedNative,
+            Lint::Unreachable => Code::Unreachable,
+            Lint::UndeclaredAssignment => Code::UndeclaredAssignment,
+            Lint::ArgumentCount => Code::ArgumentCount,
+        }
+    }
+}
+
+/// The lints that are reported, all of them unless some are allowed.
+#[derive(Debug, Clone)]
+pub struct Lints {
+    enabled: HashSet<Lint>,
+}
+
+impl Default for Lints {
+    fn default() -> Self {
+        Self {
+            enabled: Lint::ALL.into_iter().collect(),
+        }
+    }
+}
+
+impl Lints {
+    /// Stops reporting `lint`.
+    pub fn allow(&mut self, lint: Lint) {
+        self.enabled.remove(&lint);
+    }
+
+    pub fn is_enabled(&self, lint: Lint) -> bool {
+        self.enabled.contains(&lint)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Variable,
+    Parameter,
+    Function { arity: usize },
+}
+
+struct Local {
+    name: Token,
+    kind: Kind,
+    used: bool,
+    assigned: bool,
+    // argument counts and call sites, checked once it's known whether the name was reassigned
+    calls: Vec<(usize, Token)>,
+}
+
+#[derive(Default)]
+struct Scope {
+    locals: HashMap<Symbol, Local>,
+}
+
+struct Global {
+    name: Token,
+    // only a name declared once, by `fun`, is known to be that function
+    arity: Option<usize>,
+}
+
+/// Finds likely mistakes in a resolved program without running it. Scopes work like in the
+/// `Resolver`: functions see their own locals and the globals.
+pub struct Linter<'a> {
+    ast: &'a Ast,
+    lints: &'a Lints,
+    // the arity of every native function
+    natives: HashMap<Symbol, usize>,
+    scopes: Vec<Scope>,
+    globals: HashMap<Symbol, Global>,
+    // globals are only all known at the end, uses of them are checked then
+    global_calls: Vec<(Symbol, usize, Token)>,
+    global_assignments: Vec<Token>,
+    warnings: Vec<(Lint, Diagnostic)>,
+}
+
+impl<'a> Linter<'a> {
+    pub fn new(ast: &'a Ast, lints: &'a Lints, natives: HashMap<Symbol, usize>) -> Self {
+        Self {
+            ast,
+            lints,
+            natives,
+            scopes: Vec::new(),
+            globals: HashMap::new(),
+            global_calls: Vec::new(),
+            global_assignments: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// The warnings for `program`, in the order they appear in the source.
+    pub fn lint(mut self, program: &Program) -> Vec<Diagnostic> {
+        self.statements(&program.statements);
+        self.check_globals();
+
+        self.warnings
+            .sort_by_key(|(_, warning)| warning.span.map(|span| span.start));
+        // the first warning of each lint says how to turn it off
+        let mut explained = HashSet::new();
+        self.warnings
+            .into_iter()
+            .map(|(lint, mut warning)| {
+                if explained.insert(lint) {
+                    warning
+                        .notes
+                        .push(format!("`--allow={}` turns this warning off", lint.name()));
+                }
+                warning
+            })
+            .collect()
+    }
+
+    fn statements(&mut self, statements: &[StmtId]) {
+        let mut diverged: Option<&Token> = None;
+        let mut reported = false;
+        for &statement in statements {
+            // everything after is unreachable too, once is enough
+            if let Some(keyword) = diverged.filter(|_| !reported) {
+                reported = true;
+                let warning = match self.statement_token(statement) {
+                    Some(token) => Diagnostic::warning(Code::Unreachable, "Unreachable statement.")
+                        .at(Some(token))
+                        .with_labels(vec![Label::new(
+                            keyword.span,
+                            "any code after this is unreachable",
+                        )]),
+                    None => {
+                        Diagnostic::warning(Code::Unreachable, "Code after this is unreachable.")
+                            .at(Some(keyword))
+                    }
+                };
+                self.warn(Lint::Unreachable, warning);
+            }
+            self.statement(statement);
+            diverged = diverged.or_else(|| self.diverges(statement));
+        }
+    }
+
+    fn statement(&mut self, id: StmtId) {
+        let ast = self.ast;
+        match &ast[id] {
+            Stmt::Block { statements } => {
+                self.scopes.push(Scope::default());
+                self.statements(statements);
+                self.end_scope();
+            }
+            Stmt::Expression { expression } | Stmt::Print { expression } => {
+                self.expression(*expression)
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                if let Some(initializer) = *initializer {
+                    self.statement(initializer);
+                }
+                self.expression(*condition);
+                self.statement(*body);
+                if let Some(increment) = *increment {
+                    self.expression(increment);
+                }
+            }
+            Stmt::While { condition, body } => {
+                self.expression(*condition);
+                self.statement(*body);
+            }
+            Stmt::Function {
+                name, params, body, ..
+            } => {
+                self.declare(
+                    name,
+                    Kind::Function {
+                        arity: params.len(),
+                    },
+                );
+                let scopes = mem::replace(&mut self.scopes, vec![Scope::default()]);
+                for param in params {
+                    self.declare(param, Kind::Parameter);
+                }
+                self.statements(body);
+                self.end_scope();
+                self.scopes = scopes;
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(*condition);
+                self.statement(*then_branch);
+                if let Some(else_branch) = *else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::Return { value, .. } => self.expression(*value),
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+            Stmt::Var {
+                initializer, name, ..
+            } => {
+                self.expression(*initializer);
+                self.declare(name, Kind::Variable);
+            }
+            Stmt::Append {
+                array, expression, ..
+            } => {
+                self.expression(*array);
+                self.expression(*expression);
+            }
+            Stmt::Insert {
+                array,
+                index,
+                expression,
+                ..
+            } => {
+                self.expression(*array);
+                self.expression(*index);
+                self.expression(*expression);
+            }
+            Stmt::Delete { array, index, .. } => {
+                self.expression(*array);
+                self.expression(*index);
+            }
+        }
+    }
+
+    fn expression(&mut self, id: ExprId) {
+        let ast = self.ast;
+        match &ast[id] {
+            Expr::Variable { name, .. } => {
+                if let Some(local) = self.lookup(name.name()) {
+                    local.used = true;
+                }
+            }
+            Expr::Assign {
+                name, expression, ..
+            } => {
+                self.expression(*expression);
+                match self.lookup(name.name()) {
+                    Some(local) => local.assigned = true,
+                    None => self.global_assignments.push(name.clone()),
+                }
+            }
+            Expr::Call {
+                callee,
+                paren,
+                arguments,
+            } => {
+                self.expression(*callee);
+                for &argument in arguments {
+                    self.expression(argument);
+                }
+                if let Expr::Variable { name, .. } = &ast[*callee] {
+                    let call = (arguments.len(), paren.clone());
+                    match self.lookup(name.name()) {
+                        Some(local) => local.calls.push(call),
+                        None => self.global_calls.push((name.name(), call.0, call.1)),
+                    }
+                }
+            }
+            Expr::Unary { right, .. } => self.expression(*right),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.expression(*left);
+                self.expression(*right);
+            }
+            Expr::Grouping { expression } => self.expression(*expression),
+            Expr::Literal { .. } => {}
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(*condition);
+                self.expression(*then_branch);
+                self.expression(*else_branch);
+            }
+            Expr::Struct { fields } => {
+                for &(_, value) in fields {
+                    self.expression(value);
+                }
+            }
+            Expr::Array { elements } => {
+                for &element in elements {
+                    self.expression(element);
+                }
+            }
+            Expr::IndexGet { array, index, .. } => {
+                self.expression(*array);
+                self.expression(*index);
+            }
+            Expr::IndexSet {
+                array,
+                index,
+                value,
+                ..
+            } => {
+                self.expression(*array);
+                self.expression(*index);
+                self.expression(*value);
+            }
+            Expr::StructGet { target, .. } => self.expression(*target),
+            Expr::StructSet { target, value, .. } => {
+                self.expression(*target);
+                self.expression(*value);
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &Token, kind: Kind) {
+        let symbol = name.name();
+        if self.natives.contains_key(&symbol) {
+            let message = format!("`{symbol}` shadows the native function `{symbol}`.");
+            self.warn(
+                Lint::ShadowedNative,
+                Diagnostic::warning(Code::ShadowedNative, message).at(Some(name)),
+            );
+        }
+
+        let Some(scope) = self.scopes.last_mut() else {
+            let arity = match kind {
+                Kind::Function { arity } => Some(arity),
+                _ => None,
+            };
+            match self.globals.get_mut(&symbol) {
+                Some(global) => {
+                    global.arity = None;
+                    let first = global.name.span;
+                    self.redeclared(name, first);
+                }
+                None => {
+                    let name = name.clone();
+                    self.globals.insert(symbol, Global { name, arity });
+                }
+            }
+            return;
+        };
+        let local = Local {
+            name: name.clone(),
+            kind,
+            used: false,
+            assigned: false,
+            calls: Vec::new(),
+        };
+        // the new declaration takes over the name, the old one is done
+        if let Some(old) = scope.locals.insert(symbol, local) {
+            self.redeclared(name, old.name.span);
+            self.end_local(old);
+        }
+    }
+
+    fn redeclared(&mut self, name: &Token, first: Span) {
+        let message = format!("`{}` is already declared in this scope.", name.name());
+        let warning = Diagnostic::warning(Code::Redeclaration, message)
+            .at(Some(name))
+            .with_labels(vec![Label::new(first, "first declared here")]);
+        self.warn(Lint::Redeclaration, warning);
+    }
+
+    fn lookup(&mut self, name: Symbol) -> Option<&mut Local> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.locals.get_mut(&name))
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().expect("a scope was pushed");
+        let mut locals: Vec<Local> = scope.locals.into_values().collect();
+        locals.sort_by_key(|local| local.name.span.start);
+        for local in locals {
+            self.end_local(local);
+        }
+    }
+
+    // the local can't be used anymore, everything about it is known
+    fn end_local(&mut self, local: Local) {
+        let name = local.name.name();
+        if !local.used && !name.as_str().starts_with('_') {
+            let (lint, message) = match local.kind {
+                Kind::Variable => (Lint::UnusedVariable, format!("Unused variable `{name}`.")),
+                Kind::Parameter => (Lint::UnusedParameter, format!("Unused parameter `{name}`.")),
+                Kind::Function { .. } => {
+                    (Lint::UnusedVariable, format!("Unused function `{name}`."))
+                }
+            };
+            let warning = Diagnostic::warning(lint.code(), message)
+                .at(Some(&local.name))
+                .with_notes(vec![format!(
+                    "names starting with `_` are never reported, `_{name}` is fine"
+                )]);
+            self.warn(lint, warning);
+        }
+        if let Kind::Function { arity } = local.kind {
+            if !local.assigned {
+                for (count, paren) in local.calls {
+                    self.argument_count(name, arity, count, &paren, Some(&local.name));
+                }
+            }
+        }
+    }
+
+    fn check_globals(&mut self) {
+        let assigned: HashSet<Symbol> = self
+            .global_assignments
+            .iter()
+            .map(|name| name.name())
+            .collect();
+
+        for (name, count, paren) in mem::take(&mut self.global_calls) {
+            if assigned.contains(&name) {
+                continue;
+            }
+            let (arity, declaration) = match self.globals.get(&name) {
+                Some(global) => (global.arity, Some(global.name.clone())),
+                None => (self.natives.get(&name).copied(), None),
+            };
+            if let Some(arity) = arity {
+                self.argument_count(name, arity, count, &paren, declaration.as_ref());
+            }
+        }
+
+        for name in mem::take(&mut self.global_assignments) {
+            let symbol = name.name();
+            if self.globals.contains_key(&symbol) || self.natives.contains_key(&symbol) {
+                continue;
+            }
+            let candidates = self.globals.keys().map(|global| global.as_str());
+            let close = suggest::closest(symbol.as_str(), candidates);
+            let message = format!(
+                "Assignment to undeclared global `{symbol}`.{}",
+                suggest::did_you_mean(&close)
+            );
+            self.warn(
+                Lint::UndeclaredAssignment,
+                Diagnostic::warning(Code::UndeclaredAssignment, message).at(Some(&name)),
+            );
+        }
+    }
+
+    fn argument_count(
+        &mut self,
+        name: Symbol,
+        arity: usize,
+        count: usize,
+        paren: &Token,
+        declaration: Option<&Token>,
+    ) {
+        if arity == count {
+            return;
+        }
+        let plural = if arity == 1 { "" } else { "s" };
+        let message = format!("`{name}` expects {arity} argument{plural}, got {count}.");
+        let labels = declaration
+            .map(|declaration| Label::new(declaration.span, "function defined here"))
+            .into_iter()
+            .collect();
+        let warning = Diagnostic::warning(Code::ArgumentCount, message)
+            .at(Some(paren))
+            .with_labels(labels);
+        self.warn(Lint::ArgumentCount, warning);
+    }
+
+    fn warn(&mut self, lint: Lint, warning: Diagnostic) {
+        if self.lints.is_enabled(lint) {
+            self.warnings.push((lint, warning));
+        }
+    }
+
+    // the `return`, `break` or `continue` that every way through `id` ends at
+    fn diverges(&self, id: StmtId) -> Option<&'a Token> {
+        let ast = self.ast;
+        match &ast[id] {
+            Stmt::Return { keyword, .. } | Stmt::Break { keyword } | Stmt::Continue { keyword } => {
+                Some(keyword)
+            }
+            Stmt::Block { statements } => statements
+                .iter()
+                .find_map(|&statement| self.diverges(statement)),
+            Stmt::If {
+                then_branch,
+                else_branch: Some(else_branch),
+                ..
+            } => {
+                self.diverges(*else_branch)?;
+                self.diverges(*then_branch)
+            }
+            _ => None,
+        }
+    }
+
+    // a token near the start of a statement, statements don't keep their first one
+    fn statement_token(&self, id: StmtId) -> Option<&'a Token> {
+        let ast = self.ast;
+        match &ast[id] {
+            Stmt::Return { keyword, .. } | Stmt::Break { keyword } | Stmt::Continue { keyword } => {
+                Some(keyword)
+            }
+            Stmt::Var { name, .. } | Stmt::Function { name, .. } => Some(name),
+            Stmt::Append { paren, .. }
+            | Stmt::Insert { paren, .. }
+            | Stmt::Delete { paren, .. } => Some(paren),
+            Stmt::Expression { expression } | Stmt::Print { expression } => {
+                self.expression_token(*expression)
+            }
+            Stmt::Block { statements } => statements
+                .first()
+                .and_then(|&statement| self.statement_token(statement)),
+            Stmt::For {
+                initializer,
+                condition,
+                ..
+            } => initializer
+                .and_then(|initializer| self.statement_token(initializer))
+                .or_else(|| self.expression_token(*condition)),
+            Stmt::While { condition, .. } | Stmt::If { condition, .. } => {
+                self.expression_token(*condition)
+            }
+        }
+    }
+
+    fn expression_token(&self, id: ExprId) -> Option<&'a Token> {
+        let ast = self.ast;
+        match &ast[id] {
+            Expr::Variable { name, .. } | Expr::Assign { name, .. } => Some(name),
+            Expr::Unary { operator, .. } => Some(operator),
+            Expr::Binary { left, .. } | Expr::Logical { left, .. } => self.expression_token(*left),
+            Expr::Call { callee, paren, .. } => self.expression_token(*callee).or(Some(paren)),
+            Expr::Grouping { expression } => self.expression_token(*expression),
+            Expr::Ternary { condition, .. } => self.expression_token(*condition),
+            Expr::IndexGet { array, bracket, .. } | Expr::IndexSet { array, bracket, .. } => {
+                self.expression_token(*array).or(Some(bracket))
+            }
+            Expr::StructGet { target, name } | Expr::StructSet { target, name, .. } => {
+                self.expression_token(*target).or(Some(name))
+            }
+            Expr::Struct { fields } => fields.first().map(|(name, _)| name),
+            Expr::Literal { .. } | Expr::Array { .. } => None,
+        }
+    }
+}
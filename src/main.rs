@@ -2,37 +2,278 @@ use std::{
     env,
     fs::{self},
     io::{self, Write},
+    path::{Path, PathBuf},
     process::exit,
+    sync::atomic::Ordering,
+    thread,
+    time::Duration,
 };
 
-use int::interpreter::Interpreter;
+use int::{
+    cache,
+    diagnostic::ErrorFormat,
+    interpreter::{Engine, Interpreter, Outcome},
+    lint::{Lint, Lints},
+};
+
+// script calls recurse on the Rust stack, this leaves room for `MAX_CALL_DEPTH` calls even in a
+// debug build
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+// deeper than the interpreter's default, which has to fit on any thread's stack
+const MAX_CALL_DEPTH: usize = 5_000;
+
+// exit statuses, from the BSD sysexits like in clox
+// `check` found warnings but no errors
+const EXIT_WARNINGS: i32 = 1;
+const EXIT_USAGE: i32 = 64;
+const EXIT_PARSE_ERROR: i32 = 65;
+const EXIT_RUNTIME_ERROR: i32 = 70;
+const EXIT_IO_ERROR: i32 = 74;
+// ran out of steps or time, or was interrupted
+const EXIT_STOPPED: i32 = 75;
+
+struct Options {
+    engine: Engine,
+    optimize: bool,
+    max_call_depth: usize,
+    max_steps: Option<u64>,
+    time_limit: Option<Duration>,
+    memory_limit: Option<usize>,
+    cache: bool,
+    cache_dir: Option<PathBuf>,
+    error_format: ErrorFormat,
+    strict_fields: bool,
+    lints: Lints,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            engine: Engine::default(),
+            optimize: false,
+            max_call_depth: MAX_CALL_DEPTH,
+            max_steps: None,
+            time_limit: None,
+            memory_limit: None,
+            cache: true,
+            cache_dir: None,
+            error_format: ErrorFormat::default(),
+            strict_fields: false,
+            lints: Lints::default(),
+        }
+    }
+}
+
+impl Options {
+    fn interpreter(&self) -> Interpreter {
+        let mut interpreter = Interpreter::with_engine(self.engine);
+        interpreter.set_optimize(self.optimize);
+        interpreter.set_max_call_depth(self.max_call_depth);
+        interpreter.set_max_steps(self.max_steps);
+        interpreter.set_time_limit(self.time_limit);
+        interpreter.set_memory_limit(self.memory_limit);
+        interpreter.set_error_format(self.error_format);
+        interpreter.set_strict_fields(self.strict_fields);
+        interpreter
+    }
+
+    // None when caching is off or there's nowhere to put the cache
+    fn cache_path(&self, script: &str) -> Option<PathBuf> {
+        if !self.cache {
+            return None;
+        }
+        let cache_dir = self.cache_dir.clone().or_else(cache::default_dir)?;
+        Some(cache::path(Path::new(script), &cache_dir))
+    }
+}
 
 pub fn main() {
-    let args: Vec<String> = env::args().collect();
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("should spawn the interpreter thread");
+    if interpreter.join().is_err() {
+        exit(101);
+    }
+}
+
+fn run() {
+    let mut args: Vec<String> = env::args().collect();
+    let program = args.remove(0);
 
-    match args.len() {
-        1 => run_prompt(),
-        2 => run_file(&args[1]),
+    let command = args.first().map(String::as_str);
+    let (compile, check) = (command == Some("compile"), command == Some("check"));
+    if compile || check {
+        args.remove(0);
+    }
+
+    let mut options = Options::default();
+    let mut paths = Vec::new();
+    for arg in args {
+        if let Some(engine) = arg.strip_prefix("--engine=") {
+            options.engine = match engine {
+                "tree" => Engine::TreeWalker,
+                "vm" => Engine::Vm,
+                other => {
+                    eprintln!("Unknown engine `{other}`, expected `tree` or `vm`");
+                    exit(EXIT_USAGE);
+                }
+            };
+        } else if let Some(depth) = arg.strip_prefix("--max-depth=") {
+            options.max_call_depth = match depth.parse() {
+                Ok(depth) => depth,
+                Err(_) => {
+                    eprintln!("Invalid call depth `{depth}`");
+                    exit(EXIT_USAGE);
+                }
+            };
+        } else if let Some(steps) = arg.strip_prefix("--max-steps=") {
+            options.max_steps = match steps.parse() {
+                Ok(steps) => Some(steps),
+                Err(_) => {
+                    eprintln!("Invalid step count `{steps}`");
+                    exit(EXIT_USAGE);
+                }
+            };
+        } else if let Some(seconds) = arg.strip_prefix("--timeout=") {
+            options.time_limit = match seconds.parse().map(Duration::try_from_secs_f64) {
+                Ok(Ok(time_limit)) => Some(time_limit),
+                _ => {
+                    eprintln!("Invalid timeout `{seconds}`");
+                    exit(EXIT_USAGE);
+                }
+            };
+        } else if let Some(bytes) = arg.strip_prefix("--max-memory=") {
+            options.memory_limit = match bytes.parse() {
+                Ok(bytes) => Some(bytes),
+                Err(_) => {
+                    eprintln!("Invalid memory limit `{bytes}`");
+                    exit(EXIT_USAGE);
+                }
+            };
+        } else if let Some(format) = arg.strip_prefix("--error-format=") {
+            options.error_format = match format {
+                "human" => ErrorFormat::Human,
+                "json" => ErrorFormat::Json,
+                other => {
+                    eprintln!("Unknown error format `{other}`, expected `human` or `json`");
+                    exit(EXIT_USAGE);
+                }
+            };
+        } else if let Some(cache_dir) = arg.strip_prefix("--cache-dir=") {
+            options.cache_dir = Some(cache_dir.into());
+        } else if let Some(lints) = arg.strip_prefix("--allow=") {
+            for name in lints.split(',') {
+                let Some(lint) = Lint::from_name(name) else {
+                    let names: Vec<&str> = Lint::ALL.iter().map(|lint| lint.name()).collect();
+                    eprintln!(
+                        "Unknown lint `{name}`, expected one of {}",
+                        names.join(", ")
+                    );
+                    exit(EXIT_USAGE);
+                };
+                options.lints.allow(lint);
+            }
+        } else if arg == "--strict-fields" {
+            options.strict_fields = true;
+        } else if arg == "--no-cache" {
+            options.cache = false;
+        } else if arg == "-O" || arg == "--optimize" {
+            options.optimize = true;
+        } else {
+            paths.push(arg);
+        }
+    }
+
+    match paths.as_slice() {
+        [path] if compile => compile_file(path, &options),
+        [path] if check => check_file(path, &options),
+        [] if !compile && !check => run_prompt(&options),
+        [path] => run_file(path, &options),
         _ => {
-            println!("Usage: {} [script]", args[0]);
-            exit(1);
+            eprintln!(
+                "Usage: {program} [--engine=tree|vm] [-O|--optimize] [--max-depth=N] \
+                 [--max-steps=N] [--timeout=SECS] [--max-memory=BYTES] [--strict-fields] \
+                 [--cache-dir=DIR|--no-cache] [--error-format=human|json] [script]\n       \
+                 {program} compile [--cache-dir=DIR] [--error-format=human|json] script\n       \
+                 {program} check [--allow=LINT,...] [--error-format=human|json] script"
+            );
+            exit(EXIT_USAGE);
         }
     };
 }
-fn run_file(path: &str) {
-    let mut interpreter = Interpreter::default();
+fn run_file(path: &str, options: &Options) {
+    let mut interpreter = options.interpreter();
     let source = match fs::read_to_string(path) {
         Ok(source) => source,
         Err(err) => {
             eprintln!("ERROR: Couldn't read file: {err}");
-            return;
+            exit(EXIT_IO_ERROR);
         }
     };
-    interpreter.interpret(source);
+    interpreter.set_file(Some(path.into()));
+    let outcome = match options.cache_path(path) {
+        Some(cache) => interpreter.interpret_cached(source, &cache),
+        None => interpreter.interpret(source),
+    };
+    match outcome {
+        Outcome::Success => {}
+        Outcome::ParseError => exit(EXIT_PARSE_ERROR),
+        Outcome::RuntimeError => exit(EXIT_RUNTIME_ERROR),
+        Outcome::Stopped(_) => exit(EXIT_STOPPED),
+        Outcome::Exit(code) => exit(code),
+    }
 }
 
-fn run_prompt() {
-    let mut interpreter = Interpreter::default();
+// parses a script ahead of time and writes its cache, the next run starts from it
+fn compile_file(path: &str, options: &Options) {
+    let mut interpreter = options.interpreter();
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("ERROR: Couldn't read file: {err}");
+            exit(EXIT_IO_ERROR);
+        }
+    };
+    interpreter.set_file(Some(path.into()));
+    let Some(cache) = options.cache_path(path) else {
+        eprintln!("ERROR: No cache directory, set one with --cache-dir=DIR");
+        exit(EXIT_USAGE);
+    };
+    match interpreter.compile(source, &cache) {
+        Ok(true) => println!("Wrote {}", cache.display()),
+        Ok(false) => exit(EXIT_PARSE_ERROR),
+        Err(err) => {
+            eprintln!("ERROR: Couldn't write the cache: {err}");
+            exit(EXIT_IO_ERROR);
+        }
+    }
+}
+
+// reports errors and warnings without running the script
+fn check_file(path: &str, options: &Options) {
+    let mut interpreter = options.interpreter();
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("ERROR: Couldn't read file: {err}");
+            exit(EXIT_IO_ERROR);
+        }
+    };
+    interpreter.set_file(Some(path.into()));
+    match interpreter.check(source, &options.lints) {
+        Some(0) => {}
+        Some(_) => exit(EXIT_WARNINGS),
+        None => exit(EXIT_PARSE_ERROR),
+    }
+}
+
+fn run_prompt(options: &Options) {
+    let mut interpreter = options.interpreter();
+    // Ctrl-C stops the running line instead of the REPL
+    let interrupt = interpreter.interrupt_handle();
+    ctrlc::set_handler(move || interrupt.store(true, Ordering::Relaxed))
+        .expect("should set the Ctrl-C handler");
     let mut iter = io::stdin().lines();
     loop {
         print!("> ");
@@ -40,6 +281,9 @@ fn run_prompt() {
         let Some(Ok(line)) = iter.next() else {
             break;
         };
-        interpreter.interpret(line);
+        // every line keeps its own line number in errors
+        if let Outcome::Exit(code) = interpreter.interpret(line + "\n") {
+            exit(code);
+        }
     }
 }
@@ -0,0 +1,218 @@
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    mem::{self, size_of},
+    rc::{Rc, Weak},
+};
+
+use crate::{
+    diagnostic::Code,
+    gc,
+    value::{Key, Object, StructMap, Value},
+    IntError,
+};
+
+// dead objects are dropped from the list once it has grown this much since the last scan
+const MIN_SCAN: usize = 10_000;
+
+thread_local! {
+    static MEMORY: RefCell<Memory> = RefCell::new(Memory::default());
+}
+
+/// Live objects and the bytes they use, by type. Builders count as strings.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoryStats {
+    pub strings: usize,
+    pub string_bytes: usize,
+    pub arrays: usize,
+    pub array_bytes: usize,
+    pub structs: usize,
+    pub struct_bytes: usize,
+}
+
+impl MemoryStats {
+    pub fn bytes(&self) -> usize {
+        self.string_bytes + self.array_bytes + self.struct_bytes
+    }
+}
+
+enum Tracked {
+    String(Weak<String>),
+    Builder(Weak<RefCell<String>>),
+    Struct(Weak<RefCell<StructMap>>),
+    Array(Weak<RefCell<Vec<Value>>>),
+}
+
+impl Tracked {
+    fn new(object: &Object) -> Self {
+        match object {
+            Object::String(string) => Tracked::String(Rc::downgrade(string)),
+            Object::Builder(builder) => Tracked::Builder(Rc::downgrade(builder)),
+            Object::Struct(map) => Tracked::Struct(Rc::downgrade(map)),
+            Object::Array(array) => Tracked::Array(Rc::downgrade(array)),
+        }
+    }
+
+    fn upgrade(&self) -> Option<Object> {
+        match self {
+            Tracked::String(string) => string.upgrade().map(Object::String),
+            Tracked::Builder(builder) => builder.upgrade().map(Object::Builder),
+            Tracked::Struct(map) => map.upgrade().map(Object::Struct),
+            Tracked::Array(array) => array.upgrade().map(Object::Array),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Memory {
+    objects: Vec<Tracked>,
+    limit: Option<usize>,
+    // the live bytes found by the last scan plus everything counted since, never less than what
+    // is really in use
+    bytes: usize,
+    scanned: usize,
+}
+
+impl Memory {
+    fn fits(&self, bytes: usize) -> bool {
+        self.limit
+            .is_none_or(|limit| self.bytes.saturating_add(bytes) <= limit)
+    }
+}
+
+/// Going over the memory limit, which it holds.
+#[derive(Debug)]
+pub struct LimitExceeded(usize);
+
+impl Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "memory limit of {} bytes exceeded", self.0)
+    }
+}
+
+impl From<LimitExceeded> for IntError {
+    fn from(err: LimitExceeded) -> Self {
+        IntError::new(None, err.to_string()).with_code(Code::MemoryLimit)
+    }
+}
+
+/// Counts a new object. Every string, array and struct is counted when it's created, objects
+/// that grow afterwards count the growth with `grow`.
+pub fn track(object: &Object) {
+    let size = size_of_object(object);
+    let should_scan = MEMORY.with_borrow_mut(|memory| {
+        memory.objects.push(Tracked::new(object));
+        memory.bytes += size;
+        memory.objects.len() >= MIN_SCAN.max(memory.scanned * 2)
+    });
+    if should_scan {
+        scan();
+    }
+}
+
+/// Fails if `bytes` more wouldn't fit under the limit. Big allocations check first so they never
+/// happen, the object counts itself once it's created.
+pub fn reserve(bytes: usize) -> Result<(), LimitExceeded> {
+    if MEMORY.with_borrow(|memory| memory.fits(bytes)) {
+        return Ok(());
+    }
+    // the count includes everything that died since the last scan, so only fail once the live
+    // objects are known to be over the limit, with cycles collected
+    scan();
+    if MEMORY.with_borrow(|memory| memory.fits(bytes)) {
+        return Ok(());
+    }
+    gc::collect();
+    scan();
+    MEMORY.with_borrow(|memory| match memory.limit {
+        Some(limit) if !memory.fits(bytes) => Err(LimitExceeded(limit)),
+        _ => Ok(()),
+    })
+}
+
+/// `reserve` for an object that grows in place, the bytes are counted right away.
+pub fn grow(bytes: usize) -> Result<(), LimitExceeded> {
+    reserve(bytes)?;
+    MEMORY.with_borrow_mut(|memory| memory.bytes += bytes);
+    Ok(())
+}
+
+/// Fails if the objects that were created since the last check went over the limit.
+pub fn check() -> Result<(), LimitExceeded> {
+    reserve(0)
+}
+
+/// Limits the bytes used by strings, arrays and structs on this thread, going over it is a
+/// runtime error.
+pub fn set_limit(limit: Option<usize>) {
+    MEMORY.with_borrow_mut(|memory| memory.limit = limit);
+}
+
+pub fn limit() -> Option<usize> {
+    MEMORY.with_borrow(|memory| memory.limit)
+}
+
+pub fn stats() -> MemoryStats {
+    scan()
+}
+
+// measures every live object, forgets the dead ones and resets the count to what's in use
+fn scan() -> MemoryStats {
+    let tracked = MEMORY.with_borrow_mut(|memory| mem::take(&mut memory.objects));
+    let mut stats = MemoryStats::default();
+    let mut live = Vec::with_capacity(tracked.len());
+    for tracked in tracked {
+        let Some(object) = tracked.upgrade() else {
+            continue;
+        };
+        let size = size_of_object(&object);
+        match object {
+            Object::String(_) | Object::Builder(_) => {
+                stats.strings += 1;
+                stats.string_bytes += size;
+            }
+            Object::Struct(_) => {
+                stats.structs += 1;
+                stats.struct_bytes += size;
+            }
+            Object::Array(_) => {
+                stats.arrays += 1;
+                stats.array_bytes += size;
+            }
+        }
+        live.push(tracked);
+    }
+    MEMORY.with_borrow_mut(|memory| {
+        memory.objects = live;
+        memory.bytes = stats.bytes();
+        memory.scanned = memory.objects.len();
+    });
+    stats
+}
+
+// the object and its own buffer, the objects it refers to are counted by themselves. An object
+// that is being modified right now only counts its header.
+fn size_of_object(object: &Object) -> usize {
+    match object {
+        Object::String(string) => size_of::<String>() + string.capacity(),
+        Object::Builder(builder) => {
+            size_of::<RefCell<String>>()
+                + builder.try_borrow().map_or(0, |builder| builder.capacity())
+        }
+        Object::Struct(map) => {
+            size_of::<RefCell<StructMap>>()
+                + map
+                    .try_borrow()
+                    .map_or(0, |map| map.capacity() * FIELD_SIZE + map.name_bytes())
+        }
+        Object::Array(array) => {
+            size_of::<RefCell<Vec<Value>>>()
+                + array
+                    .try_borrow()
+                    .map_or(0, |array| array.capacity() * size_of::<Value>())
+        }
+    }
+}
+
+/// What a struct field takes: the entry and its slot in the index.
+pub const FIELD_SIZE: usize = size_of::<(u64, Key, Value)>() + size_of::<usize>();
@@ -1,9 +1,16 @@
-use std::{fs, time::SystemTime};
+use std::{
+    fs,
+    mem::{size_of, size_of_val},
+    time::SystemTime,
+};
 
 use crate::{
+    diagnostic::Code,
     functions::IntCallable,
+    gc,
     interpreter::Interpreter,
-    value::{Object, Value},
+    memory,
+    value::{char_range, Object, StructMap, Value},
     IntError,
 };
 
@@ -21,10 +28,10 @@ impl IntCallable for NativeClock {
     fn call(&self, _: &mut Interpreter, _: Vec<Value>) -> Result<Value, IntError> {
         match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
             Ok(f) => Ok(Value::Double(f.as_millis() as f64)),
-            Err(e) => Err(IntError::Error {
-                message: format!("Clock native function error: {e}"),
-                token: None,
-            }),
+            Err(e) => Err(IntError::new(
+                None,
+                format!("Clock native function error: {e}"),
+            )),
         }
     }
 }
@@ -43,19 +50,108 @@ impl IntCallable for Len {
     fn call(&self, _: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, IntError> {
         match &arguments[0] {
             Value::Object(Object::String(string)) => {
-                Ok(Value::Double(string.borrow().len() as f64))
+                Ok(Value::Double(string.chars().count() as f64))
+            }
+            Value::Object(Object::Builder(builder)) => {
+                Ok(Value::Double(builder.borrow().chars().count() as f64))
             }
             Value::Object(Object::Array(array)) => Ok(Value::Double(array.borrow().len() as f64)),
             Value::Object(Object::Struct(map)) => Ok(Value::Double(map.borrow().len() as f64)),
-            _ => Err(IntError::Error {
-                message: "Invalid argument to len".into(),
-                token: None,
-            }),
+            _ => Err(IntError::new(None, "Invalid argument to len").with_code(Code::TypeMismatch)),
         }
         // Ok(Value::Double(array.borrow().len() as f64))
     }
 }
 
+pub struct ByteLen;
+
+impl IntCallable for ByteLen {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> String {
+        String::from("<fun byte_len>")
+    }
+
+    fn call(&self, _: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, IntError> {
+        Ok(Value::Double(arguments[0].get_string()?.len() as f64))
+    }
+}
+
+pub struct Bytes;
+
+impl IntCallable for Bytes {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> String {
+        String::from("<fun bytes>")
+    }
+
+    fn call(&self, _: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, IntError> {
+        let string = arguments[0].get_string()?;
+        memory::reserve(string.len() * size_of::<Value>())?;
+        Ok(Value::new_array(
+            string
+                .bytes()
+                .map(|byte| Value::Double(byte as f64))
+                .collect(),
+        ))
+    }
+}
+
+pub struct Slice;
+
+impl IntCallable for Slice {
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn name(&self) -> String {
+        String::from("<fun slice>")
+    }
+
+    fn call(&self, _: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, IntError> {
+        let start = arguments[1].double()? as usize;
+        let end = arguments[2].double()? as usize;
+        match &arguments[0] {
+            Value::Object(Object::String(string)) => match char_range(string, start, end) {
+                Some(range) => {
+                    memory::reserve(range.len())?;
+                    Ok(Value::new_string(string[range].to_string()))
+                }
+                None => Err(IntError::new(
+                    None,
+                    format!(
+                        "slice `{start}..{end}` is out of bound `{len}`",
+                        len = string.chars().count()
+                    ),
+                )
+                .with_code(Code::IndexOutOfBounds)),
+            },
+            Value::Object(Object::Array(array)) => match array.borrow().get(start..end) {
+                Some(elements) => {
+                    memory::reserve(size_of_val(elements))?;
+                    Ok(Value::new_array(elements.to_vec()))
+                }
+                None => Err(IntError::new(
+                    None,
+                    format!(
+                        "slice `{start}..{end}` is out of bound `{len}`",
+                        len = array.borrow().len()
+                    ),
+                )
+                .with_code(Code::IndexOutOfBounds)),
+            },
+            _ => {
+                Err(IntError::new(None, "Invalid argument to slice").with_code(Code::TypeMismatch))
+            }
+        }
+    }
+}
+
 pub struct ArrayWithLen;
 impl IntCallable for ArrayWithLen {
     fn arity(&self) -> usize {
@@ -69,6 +165,7 @@ impl IntCallable for ArrayWithLen {
     fn call(&self, _: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, IntError> {
         let len = arguments[0].double()? as usize;
         let value = &arguments[1];
+        memory::reserve(len.saturating_mul(size_of::<Value>()))?;
         Ok(Value::new_array(
             (0..len).map(|_| value.deep_clone()).collect(),
         ))
@@ -105,6 +202,23 @@ impl IntCallable for ToString {
     }
 }
 
+pub struct Builder;
+impl IntCallable for Builder {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> String {
+        String::from("<fun builder>")
+    }
+
+    fn call(&self, _: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, IntError> {
+        let string = arguments[0].get_string()?;
+        memory::reserve(string.len())?;
+        Ok(Value::new_builder(string.to_string()))
+    }
+}
+
 pub struct ReadToString;
 impl IntCallable for ReadToString {
     fn arity(&self) -> usize {
@@ -117,9 +231,12 @@ impl IntCallable for ReadToString {
 
     fn call(&self, _: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, IntError> {
         let path = arguments[0].get_string()?;
-        let data = fs::read_to_string(path.borrow().as_str());
+        let data = fs::read_to_string(path);
         match data {
-            Ok(data) => Ok(Value::new_string(data)),
+            Ok(data) => {
+                memory::reserve(data.len())?;
+                Ok(Value::new_string(data))
+            }
             Err(_) => Ok(Value::Nil),
         }
     }
@@ -137,10 +254,190 @@ impl IntCallable for ToNum {
 
     fn call(&self, _: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, IntError> {
         let str = arguments[0].get_string()?;
-        Ok(str
-            .borrow()
-            .parse::<f64>()
-            .map(|double| Value::Double(double))
-            .unwrap_or(Value::Nil))
+        Ok(str.parse::<f64>().map(Value::Double).unwrap_or(Value::Nil))
+    }
+}
+
+pub struct Gc;
+impl IntCallable for Gc {
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn name(&self) -> String {
+        String::from("<fun gc>")
+    }
+
+    fn call(&self, _: &mut Interpreter, _: Vec<Value>) -> Result<Value, IntError> {
+        Ok(Value::Double(gc::collect() as f64))
+    }
+}
+
+pub struct GcStats;
+impl IntCallable for GcStats {
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn name(&self) -> String {
+        String::from("<fun gc_stats>")
+    }
+
+    fn call(&self, _: &mut Interpreter, _: Vec<Value>) -> Result<Value, IntError> {
+        let stats = gc::stats();
+        let mut map = StructMap::default();
+        map.insert("tracked".into(), Value::Double(stats.tracked as f64));
+        map.insert(
+            "collections".into(),
+            Value::Double(stats.collections as f64),
+        );
+        map.insert("collected".into(), Value::Double(stats.collected as f64));
+        Ok(Value::new_struct(map))
+    }
+}
+
+pub struct MemoryStats;
+impl IntCallable for MemoryStats {
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn name(&self) -> String {
+        String::from("<fun memory_stats>")
+    }
+
+    fn call(&self, _: &mut Interpreter, _: Vec<Value>) -> Result<Value, IntError> {
+        let stats = memory::stats();
+        let mut map = StructMap::default();
+        map.insert("strings".into(), Value::Double(stats.strings as f64));
+        map.insert(
+            "string_bytes".into(),
+            Value::Double(stats.string_bytes as f64),
+        );
+        map.insert("arrays".into(), Value::Double(stats.arrays as f64));
+        map.insert(
+            "array_bytes".into(),
+            Value::Double(stats.array_bytes as f64),
+        );
+        map.insert("structs".into(), Value::Double(stats.structs as f64));
+        map.insert(
+            "struct_bytes".into(),
+            Value::Double(stats.struct_bytes as f64),
+        );
+        map.insert("bytes".into(), Value::Double(stats.bytes() as f64));
+        map.insert(
+            "limit".into(),
+            memory::limit().map_or(Value::Nil, |limit| Value::Double(limit as f64)),
+        );
+        Ok(Value::new_struct(map))
+    }
+}
+
+pub struct Keys;
+impl IntCallable for Keys {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> String {
+        String::from("<fun keys>")
+    }
+
+    fn call(&self, _: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, IntError> {
+        let map = arguments[0].get_struct()?.borrow();
+        Ok(Value::new_array(
+            map.keys()
+                .map(|key| Value::new_string(key.as_str().to_string()))
+                .collect(),
+        ))
+    }
+}
+
+/// `try(f)` calls `f` without arguments. It returns `{ok: true, value}`, or `{ok: false, error,
+/// trace}` if the call failed, where `trace` lists the calls that led to the error, innermost
+/// first, as `{function, line}`.
+pub struct Try;
+impl IntCallable for Try {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> String {
+        String::from("<fun try>")
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, IntError> {
+        let fun = arguments.into_iter().next().unwrap().get_fun()?;
+        let mut map = StructMap::default();
+        match interpreter.call_from_native(&fun, Vec::new()) {
+            Ok(value) => {
+                map.insert("ok".into(), Value::Bool(true));
+                map.insert("value".into(), value);
+            }
+            Err(IntError::Error {
+                code,
+                message,
+                token,
+                trace,
+                ..
+            }) => {
+                let trace = interpreter
+                    .traceback(token.as_ref(), &trace)
+                    .into_iter()
+                    .map(|(function, line)| {
+                        let mut frame = StructMap::default();
+                        frame.insert("function".into(), Value::new_string(function));
+                        frame.insert(
+                            "line".into(),
+                            line.map_or(Value::Nil, |line| Value::Double(line as f64)),
+                        );
+                        Value::new_struct(frame)
+                    })
+                    .collect();
+                map.insert("ok".into(), Value::Bool(false));
+                map.insert("error".into(), Value::new_string(message));
+                map.insert("code".into(), Value::new_string(code.as_str().into()));
+                map.insert("trace".into(), Value::new_array(trace));
+            }
+            // the budget stays spent, the caller stops at its next step unless it was an
+            // interrupt, which is used up
+            Err(IntError::Stopped(limit)) => {
+                map.insert("ok".into(), Value::Bool(false));
+                map.insert("error".into(), Value::new_string(limit.to_string()));
+                map.insert(
+                    "code".into(),
+                    Value::new_string(Code::Stopped.as_str().into()),
+                );
+                map.insert("stopped".into(), Value::Bool(true));
+                map.insert("trace".into(), Value::new_array(Vec::new()));
+            }
+            // `exit` can't be caught
+            Err(err) => return Err(err),
+        }
+        Ok(Value::new_struct(map))
+    }
+}
+
+/// `exit(code)` ends the program with the exit status `code`, from 0 to 255.
+pub struct Exit;
+impl IntCallable for Exit {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn name(&self) -> String {
+        String::from("<fun exit>")
+    }
+
+    fn call(&self, _: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, IntError> {
+        let code = arguments[0].double()?;
+        if code.fract() != 0.0 || !(0.0..=255.0).contains(&code) {
+            return Err(format!("Exit code must be an integer from 0 to 255, got {code}").into());
+        }
+        Err(IntError::Exit(code as i32))
     }
 }
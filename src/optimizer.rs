@@ -0,0 +1,276 @@
+use std::mem;
+
+use crate::{
+    ast::{Ast, ExprId, StmtId},
+    expression::{Expr, Grouping, Literal},
+    interpreter::{binary, unary},
+    statement::{Block, Stmt},
+    token::TokenKind,
+    value::Value,
+};
+
+/// Folds constant expressions and removes code that can never run.
+///
+/// It runs after the resolver, so a program reports the same errors with and without it, and
+/// an operation is only folded when it succeeds: `1 + true` is left alone and still fails at
+/// runtime.
+pub fn optimize(ast: &mut Ast, statements: &mut Vec<StmtId>) {
+    optimize_statements(ast, statements);
+}
+
+fn optimize_statements(ast: &mut Ast, statements: &mut Vec<StmtId>) {
+    for &statement in statements.iter() {
+        optimize_statement(ast, statement);
+    }
+    // whatever follows a `return`, `break` or `continue` in the same block never runs
+    if let Some(end) = statements.iter().position(|&statement| {
+        matches!(
+            ast[statement],
+            Stmt::Return { .. } | Stmt::Break { .. } | Stmt::Continue { .. }
+        )
+    }) {
+        statements.truncate(end + 1);
+    }
+    statements.retain(
+        |&statement| !matches!(&ast[statement], Stmt::Block { statements } if statements.is_empty()),
+    );
+}
+
+// a node is taken out of the ast while its children are optimized and put back, maybe replaced
+fn optimize_statement(ast: &mut Ast, id: StmtId) {
+    let mut statement = take_statement(ast, id);
+    match &mut statement {
+        Stmt::Block { statements } => optimize_statements(ast, statements),
+        Stmt::Expression { expression }
+        | Stmt::Print { expression }
+        | Stmt::Return {
+            value: expression, ..
+        }
+        | Stmt::Var {
+            initializer: expression,
+            ..
+        } => optimize_expression(ast, *expression),
+        Stmt::Function { body, .. } => optimize_statements(ast, body),
+        Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            optimize_expression(ast, *condition);
+            optimize_statement(ast, *then_branch);
+            if let Some(else_branch) = *else_branch {
+                optimize_statement(ast, else_branch);
+            }
+            if let Some(condition) = literal(ast, *condition) {
+                statement = if condition.is_truthy() {
+                    take_statement(ast, *then_branch)
+                } else {
+                    match *else_branch {
+                        Some(else_branch) => take_statement(ast, else_branch),
+                        None => Block(Vec::new()),
+                    }
+                };
+            }
+        }
+        Stmt::While { condition, body } => {
+            optimize_expression(ast, *condition);
+            optimize_statement(ast, *body);
+            if literal(ast, *condition).is_some_and(|condition| !condition.is_truthy()) {
+                statement = Block(Vec::new());
+            }
+        }
+        Stmt::For {
+            initializer,
+            condition,
+            increment,
+            body,
+        } => {
+            if let Some(initializer) = *initializer {
+                optimize_statement(ast, initializer);
+            }
+            optimize_expression(ast, *condition);
+            if let Some(increment) = *increment {
+                optimize_expression(ast, increment);
+            }
+            optimize_statement(ast, *body);
+            // the initializer still runs once
+            if literal(ast, *condition).is_some_and(|condition| !condition.is_truthy()) {
+                statement = match *initializer {
+                    Some(initializer) => take_statement(ast, initializer),
+                    None => Block(Vec::new()),
+                };
+            }
+        }
+        Stmt::Break { .. } | Stmt::Continue { .. } => {}
+        Stmt::Append {
+            array, expression, ..
+        } => {
+            optimize_expression(ast, *expression);
+            optimize_place(ast, *array);
+        }
+        Stmt::Insert {
+            array,
+            index,
+            expression,
+            ..
+        } => {
+            optimize_expression(ast, *array);
+            optimize_expression(ast, *index);
+            optimize_expression(ast, *expression);
+        }
+        Stmt::Delete { array, index, .. } => {
+            optimize_expression(ast, *array);
+            optimize_expression(ast, *index);
+        }
+    }
+    ast[id] = statement;
+}
+
+fn optimize_expression(ast: &mut Ast, id: ExprId) {
+    match &ast[id] {
+        Expr::Unary { right, .. } => optimize_expression(ast, *right),
+        &Expr::Binary { left, right, .. } | &Expr::Logical { left, right, .. } => {
+            optimize_expression(ast, left);
+            optimize_expression(ast, right);
+        }
+        Expr::Call {
+            callee, arguments, ..
+        } => {
+            let arguments = arguments.clone();
+            optimize_expression(ast, *callee);
+            for argument in arguments {
+                optimize_expression(ast, argument);
+            }
+        }
+        Expr::Grouping { expression } => optimize_expression(ast, *expression),
+        Expr::Literal { .. } | Expr::Variable { .. } => {}
+        &Expr::Ternary {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            optimize_expression(ast, condition);
+            optimize_expression(ast, then_branch);
+            optimize_expression(ast, else_branch);
+        }
+        Expr::Assign { expression, .. } => optimize_expression(ast, *expression),
+        Expr::Struct { fields } => {
+            let values: Vec<ExprId> = fields.iter().map(|&(_, value)| value).collect();
+            for value in values {
+                optimize_expression(ast, value);
+            }
+        }
+        Expr::Array { elements } => {
+            for element in elements.clone() {
+                optimize_expression(ast, element);
+            }
+        }
+        &Expr::IndexGet { array, index, .. } => {
+            optimize_expression(ast, array);
+            optimize_expression(ast, index);
+        }
+        &Expr::IndexSet {
+            array,
+            index,
+            value,
+            ..
+        } => {
+            optimize_place(ast, array);
+            optimize_expression(ast, index);
+            optimize_expression(ast, value);
+        }
+        Expr::StructGet { target, .. } => optimize_expression(ast, *target),
+        &Expr::StructSet { target, value, .. } => {
+            optimize_expression(ast, target);
+            optimize_expression(ast, value);
+        }
+    }
+    if let Some(folded) = fold(ast, id) {
+        ast[id] = folded;
+    }
+}
+
+// the children are already optimized
+fn fold(ast: &mut Ast, id: ExprId) -> Option<Expr> {
+    match &ast[id] {
+        Expr::Grouping { expression } => Some(take(ast, *expression)),
+        Expr::Unary { operator, right } => unary(operator, literal(ast, *right)?.clone())
+            .ok()
+            .map(Literal),
+        Expr::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            if operator.kind == TokenKind::Comma {
+                literal(ast, *left)?;
+                return Some(take(ast, *right));
+            }
+            binary(
+                operator,
+                literal(ast, *left)?.clone(),
+                literal(ast, *right)?.clone(),
+            )
+            .ok()
+            .map(Literal)
+        }
+        &Expr::Logical {
+            left,
+            ref operator,
+            right,
+        } => {
+            let left_wins = match operator.kind {
+                TokenKind::And => !literal(ast, left)?.is_truthy(),
+                _ => literal(ast, left)?.is_truthy(),
+            };
+            Some(take(ast, if left_wins { left } else { right }))
+        }
+        &Expr::Ternary {
+            condition,
+            then_branch,
+            else_branch,
+        } => Some(take(
+            ast,
+            if literal(ast, condition)?.is_truthy() {
+                then_branch
+            } else {
+                else_branch
+            },
+        )),
+        _ => None,
+    }
+}
+
+// a string can only be written back to a variable, a field or an element, so an expression that
+// wasn't one of those must not become one: `append((s), "x")` keeps failing
+fn optimize_place(ast: &mut Ast, id: ExprId) {
+    let was_place = is_place(&ast[id]);
+    optimize_expression(ast, id);
+    if !was_place && is_place(&ast[id]) {
+        let expression = take(ast, id);
+        let expression = ast.add_expression(expression);
+        ast[id] = Grouping(expression);
+    }
+}
+
+fn is_place(expression: &Expr) -> bool {
+    matches!(
+        expression,
+        Expr::Variable { .. } | Expr::StructGet { .. } | Expr::IndexGet { .. }
+    )
+}
+
+fn literal(ast: &Ast, id: ExprId) -> Option<&Value> {
+    match &ast[id] {
+        Expr::Literal { value } => Some(value),
+        _ => None,
+    }
+}
+
+fn take(ast: &mut Ast, id: ExprId) -> Expr {
+    mem::replace(&mut ast[id], Literal(Value::Nil))
+}
+
+fn take_statement(ast: &mut Ast, id: StmtId) -> Stmt {
+    mem::replace(&mut ast[id], Block(Vec::new()))
+}
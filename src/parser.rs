@@ -1,10 +1,14 @@
+use std::mem;
+
 use crate::{
+    ast::{Ast, ExprId, StmtId},
+    diagnostic::{Code, Diagnostic, Label},
+    environment::Slot,
     expression::{
         Array, Assign, Binary, Call, Expr, Grouping, IndexGet, IndexSet, Literal, Logical, Struct,
         StructGet, StructSet, Ternary, Unary, Variable,
     },
-    functions::Function,
-    scanner::Scanner,
+    scanner::{string_literal_value, Scanner},
     statement::{
         Append, Block, Break, Continue, Delete, Expression, For, Function, If, Insert, Print,
         Return, Stmt, Var, While,
@@ -18,9 +22,12 @@ use crate::{
 pub struct Parser {
     tokens: Vec<Token>,
     pub source: String,
-    pub statements: Vec<Stmt>,
+    pub ast: Ast,
     current: usize,
-    had_error: bool,
+    // the scanner's errors and the parser's
+    diagnostics: Vec<Diagnostic>,
+    /// Things that parse but are probably mistakes, they don't stop the program.
+    pub warnings: Vec<Diagnostic>,
 }
 
 // like the function match_token, used on patterns that carry data like String or Double.
@@ -45,39 +52,64 @@ impl Parser {
         Self {
             tokens: scanner.tokens,
             source: scanner.source,
-            statements: Vec::new(),
+            ast: Ast::default(),
             current: 0,
-            had_error: false,
+            diagnostics: scanner.diagnostics,
+            warnings: Vec::new(),
         }
     }
 
-    pub fn parse(&mut self) {
+    /// The statements of the program, their nodes are in `ast`. After an error the parser skips
+    /// to the next statement so that one call reports every error.
+    pub fn parse(&mut self) -> Result<Vec<StmtId>, Vec<Diagnostic>> {
+        let mut statements = Vec::new();
         while !self.is_at_end() {
             let statement = self.declaration();
             match statement {
-                Ok(statement) => self.statements.push(statement),
-                Err(IntError::Error { message, token }) => {
-                    self.had_error = true;
+                Ok(statement) => statements.push(statement),
+                Err(IntError::Error {
+                    code,
+                    message,
+                    token,
+                    labels,
+                    ..
+                }) => {
                     self.syncronize();
-                    match token {
-                        Some(token) => println!(
-                            "{message} At token: `{}` at line: {}",
-                            self.lexeme(&token),
-                            token.line
-                        ),
-                        None => println!("{message}"),
+                    // the scanner already explained what's wrong with an error token
+                    if token
+                        .as_ref()
+                        .is_some_and(|token| token.kind == TokenKind::Error)
+                    {
+                        continue;
                     }
+                    self.diagnostics.push(
+                        Diagnostic::error(code, message)
+                            .at(token.as_ref())
+                            .with_labels(labels),
+                    );
                 }
-                Err(IntError::ReturnValue(_, _) | IntError::Break(_) | IntError::Continue(_)) => {
+                Err(
+                    IntError::ReturnValue(_, _)
+                    | IntError::TailCall(_, _)
+                    | IntError::Break(_)
+                    | IntError::Continue(_)
+                    | IntError::Stopped(_)
+                    | IntError::Exit(_),
+                ) => {
                     unreachable!(
                         "return/break/continue are only invoked while intepreting, not parsing"
                     )
                 }
             }
         }
+        if self.diagnostics.is_empty() {
+            Ok(statements)
+        } else {
+            Err(mem::take(&mut self.diagnostics))
+        }
     }
 
-    fn declaration(&mut self) -> Result<Stmt, IntError> {
+    fn declaration(&mut self) -> Result<StmtId, IntError> {
         if self.match_token(TokenKind::Fun) {
             self.function("function")
         } else if self.match_token(TokenKind::Var) {
@@ -87,7 +119,7 @@ impl Parser {
         }
     }
 
-    fn function(&mut self, kind: &str) -> Result<Stmt, IntError> {
+    fn function(&mut self, kind: &str) -> Result<StmtId, IntError> {
         let name = self.consume(TokenKind::Identifier, &format!("Expected {kind} name."))?;
         self.consume(
             TokenKind::LeftParen,
@@ -116,30 +148,27 @@ impl Parser {
         )?;
 
         let body = self.block()?;
-        Ok(Function(Function::new(
-            self.lexeme(&name).to_string(),
-            parameters,
-            body,
-        )))
+        Ok(self.statement_node(Function(name, parameters, body, Slot::Global)))
     }
 
-    fn var_declaration(&mut self) -> Result<Stmt, IntError> {
+    fn var_declaration(&mut self) -> Result<StmtId, IntError> {
         let name = self.consume(TokenKind::Identifier, "Expected a variable name")?;
 
-        let mut initializer = Literal(Value::Nil);
-        if self.match_token(TokenKind::Equal) {
-            initializer = self.expression()?;
-        }
+        let initializer = if self.match_token(TokenKind::Equal) {
+            self.expression()?
+        } else {
+            self.expression_node(Literal(Value::Nil))
+        };
 
         self.consume(
             TokenKind::Semicolon,
             "Expected `;` after variable declaration.",
         )?;
 
-        Ok(Var(initializer, name))
+        Ok(self.statement_node(Var(initializer, name, Slot::Global)))
     }
 
-    fn statement(&mut self) -> Result<Stmt, IntError> {
+    fn statement(&mut self) -> Result<StmtId, IntError> {
         if self.match_token(TokenKind::For) {
             return self.for_statement();
         }
@@ -171,70 +200,70 @@ impl Parser {
             return self.delete_statement();
         }
         if self.match_token(TokenKind::LeftBrace) {
-            return Ok(Block(self.block()?));
+            let statements = self.block()?;
+            return Ok(self.statement_node(Block(statements)));
         }
 
         self.expression_statement()
     }
 
-    fn delete_statement(&mut self) -> Result<Stmt, IntError> {
+    fn delete_statement(&mut self) -> Result<StmtId, IntError> {
         let paren = self.consume(TokenKind::LeftParen, "Expected `(` after delete.")?;
-        let Expr::IndexGet { array, index, .. } = self.assignment()? else {
-            return Err(IntError::Error {
-                message: "Invalid delete target.".into(),
-                token: Some(paren.clone()),
-            });
+        let target = self.assignment()?;
+        let Expr::IndexGet { array, index, .. } = self.ast[target] else {
+            return Err(IntError::new(Some(&paren), "Invalid delete target.")
+                .with_code(Code::InvalidTarget));
         };
         self.consume(TokenKind::RightParen, "Expected `)` after delete.")?;
         self.consume(TokenKind::Semicolon, "Expected `;` after delete.")?;
-        Ok(Delete(paren, *array, *index))
+        Ok(self.statement_node(Delete(paren, array, index)))
     }
 
-    fn insert_statement(&mut self) -> Result<Stmt, IntError> {
+    fn insert_statement(&mut self) -> Result<StmtId, IntError> {
         let paren = self.consume(TokenKind::LeftParen, "Expected `(` after insert.")?;
-        let Expr::IndexGet { array, index, .. } = self.assignment()? else {
-            return Err(IntError::Error {
-                message: "Invalid insert target.".into(),
-                token: Some(paren.clone()),
-            });
+        let target = self.assignment()?;
+        let Expr::IndexGet { array, index, .. } = self.ast[target] else {
+            return Err(IntError::new(Some(&paren), "Invalid insert target.")
+                .with_code(Code::InvalidTarget));
         };
         self.consume(TokenKind::Comma, "Expected `,` after array")?;
         let expression = self.assignment()?;
         self.consume(TokenKind::RightParen, "Expected `)` after insert.")?;
         self.consume(TokenKind::Semicolon, "Expected `;` after insert.")?;
-        Ok(Insert(paren, *array, *index, expression))
+        Ok(self.statement_node(Insert(paren, array, index, expression)))
     }
 
-    fn append_statement(&mut self) -> Result<Stmt, IntError> {
+    fn append_statement(&mut self) -> Result<StmtId, IntError> {
         let paren = self.consume(TokenKind::LeftParen, "Expected `(` after append.")?;
         let array = self.assignment()?;
         self.consume(TokenKind::Comma, "Expected `,` after array")?;
         let expression = self.assignment()?;
         self.consume(TokenKind::RightParen, "Expected `)` after append.")?;
         self.consume(TokenKind::Semicolon, "Expected `;` after append.")?;
-        Ok(Append(paren, array, expression))
+        Ok(self.statement_node(Append(paren, array, expression)))
     }
 
-    fn break_statement(&mut self, keyword: Token) -> Result<Stmt, IntError> {
+    fn break_statement(&mut self, keyword: Token) -> Result<StmtId, IntError> {
         self.consume(TokenKind::Semicolon, "Expected `;` after break.")?;
-        Ok(Break(keyword))
+        Ok(self.statement_node(Break(keyword)))
     }
 
-    fn continue_statement(&mut self, keyword: Token) -> Result<Stmt, IntError> {
+    fn continue_statement(&mut self, keyword: Token) -> Result<StmtId, IntError> {
         self.consume(TokenKind::Semicolon, "Expected `;` after continue.")?;
-        Ok(Continue(keyword))
+        Ok(self.statement_node(Continue(keyword)))
     }
 
-    fn return_statement(&mut self, keyword: Token) -> Result<Stmt, IntError> {
+    fn return_statement(&mut self, keyword: Token) -> Result<StmtId, IntError> {
         if self.match_token(TokenKind::Semicolon) {
-            return Ok(Return(keyword, Literal(Value::Nil)));
+            let value = self.expression_node(Literal(Value::Nil));
+            return Ok(self.statement_node(Return(keyword, value)));
         }
         let value = self.expression()?;
         self.consume(TokenKind::Semicolon, "Expected `;` after return.")?;
-        Ok(Return(keyword, value))
+        Ok(self.statement_node(Return(keyword, value)))
     }
 
-    fn for_statement(&mut self) -> Result<Stmt, IntError> {
+    fn for_statement(&mut self) -> Result<StmtId, IntError> {
         self.consume(TokenKind::LeftParen, "Expected `(` after 'for'.")?;
         let initializer = if self.match_token(TokenKind::Semicolon) {
             None
@@ -245,7 +274,7 @@ impl Parser {
         };
 
         let condition = if self.match_token(TokenKind::Semicolon) {
-            Literal(Value::Bool(true))
+            self.expression_node(Literal(Value::Bool(true)))
         } else {
             let condition = self.expression()?;
             self.consume(TokenKind::Semicolon, "Expected `;` after loop condition.")?;
@@ -262,19 +291,20 @@ impl Parser {
 
         let body = self.statement()?;
 
-        Ok(Block(vec![For(initializer, condition, increment, body)]))
+        let for_statement = self.statement_node(For(initializer, condition, increment, body));
+        Ok(self.statement_node(Block(vec![for_statement])))
     }
 
-    fn while_statement(&mut self) -> Result<Stmt, IntError> {
+    fn while_statement(&mut self) -> Result<StmtId, IntError> {
         self.consume(TokenKind::LeftParen, "Expected `(` after `while`.")?;
         let condition = self.expression()?;
         self.consume(TokenKind::RightParen, "Expected `(` after condition.")?;
         let body = self.statement()?;
 
-        Ok(While(condition, body))
+        Ok(self.statement_node(While(condition, body)))
     }
 
-    fn if_statement(&mut self) -> Result<Stmt, IntError> {
+    fn if_statement(&mut self) -> Result<StmtId, IntError> {
         self.consume(TokenKind::LeftParen, "Expected `(` after `if`.")?;
         let condition = self.expression()?;
         self.consume(TokenKind::RightParen, "Expected `)` after `if` condition.")?;
@@ -285,10 +315,11 @@ impl Parser {
             else_branch = Some(self.statement()?);
         };
 
-        Ok(If(condition, then_branch, else_branch))
+        Ok(self.statement_node(If(condition, then_branch, else_branch)))
     }
 
-    fn block(&mut self) -> Result<Vec<Stmt>, IntError> {
+    fn block(&mut self) -> Result<Vec<StmtId>, IntError> {
+        let brace = self.previous();
         let mut statements = Vec::new();
 
         while let Some(token) = self.tokens.get(self.current) {
@@ -298,138 +329,143 @@ impl Parser {
             statements.push(self.declaration()?);
         }
 
-        self.consume(TokenKind::RightBrace, "Expected `}` after block.")?;
+        self.consume_closing(TokenKind::RightBrace, &brace, "Expected `}` after block.")?;
 
         Ok(statements)
     }
 
-    fn print_statement(&mut self) -> Result<Stmt, IntError> {
+    fn print_statement(&mut self) -> Result<StmtId, IntError> {
         let value = self.expression()?;
         self.consume(TokenKind::Semicolon, "Expected `;` after value.")?;
-        Ok(Print(value))
+        Ok(self.statement_node(Print(value)))
     }
 
-    fn expression_statement(&mut self) -> Result<Stmt, IntError> {
+    fn expression_statement(&mut self) -> Result<StmtId, IntError> {
         let expr = self.expression()?;
         self.consume(TokenKind::Semicolon, "Expected `;` after value.")?;
-        Ok(Expression(expr))
+        Ok(self.statement_node(Expression(expr)))
     }
 
-    fn expression(&mut self) -> Result<Expr, IntError> {
+    fn expression(&mut self) -> Result<ExprId, IntError> {
         self.comma()
     }
 
-    fn comma(&mut self) -> Result<Expr, IntError> {
+    fn comma(&mut self) -> Result<ExprId, IntError> {
         let mut expr = self.assignment()?;
 
         match_token!(self, while operator TokenKind::Comma, {
             let right = self.assignment()?;
-            expr = Binary(expr, operator, right);
+            expr = self.expression_node(Binary(expr, operator, right));
         });
 
         Ok(expr)
     }
 
-    fn assignment(&mut self) -> Result<Expr, IntError> {
+    fn assignment(&mut self) -> Result<ExprId, IntError> {
         let left = self.ternary()?;
 
         match_token!(self, if equals TokenKind::Equal, {
             let value = self.assignment()?;
-            if let Expr::Variable { name } = left {
-                return Ok(Assign(*name, value));
-            }
-
-            if let Expr::StructGet { target, name } = left {
-                return Ok(StructSet(*target, *name, value));
-            }
-
-            if let Expr::IndexGet { array, bracket, index } = left {
-                return Ok(IndexSet(*array, *bracket, *index, value));
-            }
-
-            return Err(IntError::Error { message: "Invalid assignment target".into(), token: Some(equals) });
+            // the target turns into the assignment and keeps its id
+            let assignment = match &self.ast[left] {
+                Expr::Variable { name, .. } => Assign(name.clone(), value, Slot::Global),
+                Expr::StructGet { target, name } => StructSet(*target, name.clone(), value),
+                Expr::IndexGet { array, bracket, index } => {
+                    IndexSet(*array, bracket.clone(), *index, value)
+                }
+                _ => return Err(IntError::new(Some(&equals), "Invalid assignment target").with_code(Code::InvalidTarget)),
+            };
+            self.ast[left] = assignment;
+            return Ok(left);
         });
 
         Ok(left)
     }
 
-    fn ternary(&mut self) -> Result<Expr, IntError> {
+    fn ternary(&mut self) -> Result<ExprId, IntError> {
         let mut expr = self.or()?;
         if self.match_token(TokenKind::Question) {
             let then_branch = self.expression()?;
             self.consume(TokenKind::Colon, "Expected `:` after ternary condition")?;
             let else_branch = self.ternary()?;
-            expr = Ternary(expr, then_branch, else_branch);
+            expr = self.expression_node(Ternary(expr, then_branch, else_branch));
         }
 
         Ok(expr)
     }
 
-    fn or(&mut self) -> Result<Expr, IntError> {
+    fn or(&mut self) -> Result<ExprId, IntError> {
         let mut expr = self.and()?;
         match_token!(self, while operator TokenKind::Or, {
             let right = self.and()?;
-            expr = Logical(expr, operator, right);
+            expr = self.expression_node(Logical(expr, operator, right));
         });
 
         Ok(expr)
     }
 
-    fn and(&mut self) -> Result<Expr, IntError> {
+    fn and(&mut self) -> Result<ExprId, IntError> {
         let mut expr = self.equality()?;
         match_token!(self, while operator TokenKind::And, {
             let right = self.equality()?;
-            expr = Logical(expr, operator, right);
+            expr = self.expression_node(Logical(expr, operator, right));
         });
 
         Ok(expr)
     }
 
-    fn equality(&mut self) -> Result<Expr, IntError> {
+    fn equality(&mut self) -> Result<ExprId, IntError> {
         let mut expr = self.comparison()?;
-        match_token!(self, while operator TokenKind::BangEqual | TokenKind::EqualEqual, {
+        match_token!(self, while operator TokenKind::BangEqual | TokenKind::EqualEqual | TokenKind::Is, {
+            let mut operator = operator;
+            match_token!(self, if not TokenKind::Not, {
+                if operator.kind != TokenKind::Is {
+                    return Err(IntError::new(Some(&not), "`not` only goes after `is`, use `!` to negate a value").with_code(Code::Syntax));
+                }
+                operator = Token::new(TokenKind::IsNot, (operator.span.start, not.span.end), operator.line);
+            });
             let right = self.comparison()?;
-            expr = Binary(expr, operator, right);
+            expr = self.expression_node(Binary(expr, operator, right));
         });
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expr, IntError> {
+    fn comparison(&mut self) -> Result<ExprId, IntError> {
         let mut expr = self.term()?;
         match_token!(self, while operator TokenKind::Greater | TokenKind::GreaterEqual | TokenKind::Less | TokenKind::LessEqual , {
             let right = self.term()?;
-            expr = Binary(expr, operator, right);
+            expr = self.expression_node(Binary(expr, operator, right));
         });
         Ok(expr)
     }
 
-    fn term(&mut self) -> Result<Expr, IntError> {
+    fn term(&mut self) -> Result<ExprId, IntError> {
         let mut expr = self.factor()?;
         match_token!(self, while operator TokenKind::Minus | TokenKind::Plus, {
             let right = self.factor()?;
-            expr = Binary(expr, operator, right);
+            expr = self.expression_node(Binary(expr, operator, right));
         });
         Ok(expr)
     }
 
-    fn factor(&mut self) -> Result<Expr, IntError> {
+    fn factor(&mut self) -> Result<ExprId, IntError> {
         let mut expr = self.unary()?;
         match_token!(self, while operator TokenKind::Slash | TokenKind::Star, {
             let right = self.unary()?;
-            expr = Binary(expr, operator, right);
+            expr = self.expression_node(Binary(expr, operator, right));
         });
         Ok(expr)
     }
 
-    fn unary(&mut self) -> Result<Expr, IntError> {
+    fn unary(&mut self) -> Result<ExprId, IntError> {
         match_token!(self, if operator TokenKind::Bang | TokenKind::Minus, {
             let right = self.unary()?;
-            return Ok(Unary(operator, right));
+            return Ok(self.expression_node(Unary(operator, right)));
         });
         self.call()
     }
 
-    fn call(&mut self) -> Result<Expr, IntError> {
+    fn call(&mut self) -> Result<ExprId, IntError> {
         let mut expr = self.primary()?;
 
         loop {
@@ -440,12 +476,16 @@ impl Parser {
                     TokenKind::Identifier,
                     "Expected struct field name after `.`.",
                 )?;
-                expr = StructGet(expr, name);
+                expr = self.expression_node(StructGet(expr, name));
             } else if self.match_token(TokenKind::LeftBracket) {
+                let open = self.previous();
                 let index = self.expression()?;
-                let bracket =
-                    self.consume(TokenKind::RightBracket, "Expected `]` after array index.")?;
-                expr = IndexGet(expr, bracket, index);
+                let bracket = self.consume_closing(
+                    TokenKind::RightBracket,
+                    &open,
+                    "Expected `]` after array index.",
+                )?;
+                expr = self.expression_node(IndexGet(expr, bracket, index));
             } else {
                 break;
             }
@@ -454,7 +494,8 @@ impl Parser {
         Ok(expr)
     }
 
-    fn finish_call(&mut self, callee: Expr) -> Result<Expr, IntError> {
+    fn finish_call(&mut self, callee: ExprId) -> Result<ExprId, IntError> {
+        let open = self.previous();
         let mut arguments = Vec::new();
         if let Some(token) = self.tokens.get(self.current) {
             if token.kind != TokenKind::RightParen {
@@ -468,62 +509,72 @@ impl Parser {
             }
         }
 
-        let paren = self.consume(TokenKind::RightParen, "Expected `)` after arguments")?;
+        let paren =
+            self.consume_closing(TokenKind::RightParen, &open, "Expected `)` after arguments")?;
 
-        Ok(Call(callee, paren, arguments))
+        Ok(self.expression_node(Call(callee, paren, arguments)))
     }
 
-    fn primary(&mut self) -> Result<Expr, IntError> {
+    fn primary(&mut self) -> Result<ExprId, IntError> {
         if self.match_token(TokenKind::False) {
-            return Ok(Literal(Value::Bool(false)));
+            return Ok(self.expression_node(Literal(Value::Bool(false))));
         }
         if self.match_token(TokenKind::True) {
-            return Ok(Literal(Value::Bool(true)));
+            return Ok(self.expression_node(Literal(Value::Bool(true))));
         }
         if self.match_token(TokenKind::Nil) {
-            return Ok(Literal(Value::Nil));
+            return Ok(self.expression_node(Literal(Value::Nil)));
         }
         match_token!(self, if token TokenKind::String, {
-            let value = self.lexeme(&token);
-            return Ok(Literal(Value::new_string(value[1..value.len()-1].to_string())));
+            let (value, warnings) = string_literal_value(self.lexeme(&token));
+            for warning in warnings {
+                self.warnings.push(Diagnostic::warning(Code::InvalidEscape, warning).at(Some(&token)));
+            }
+            return Ok(self.expression_node(Literal(Value::new_string(value))));
         });
         match_token!(self, if token TokenKind::Number, {
             let lexeme = self.lexeme(&token);
-            return if lexeme.starts_with("0x") {
+            return if let Some(hex) = lexeme.strip_prefix("0x") {
                 // TODO: this expect might crash on very large values
-                let value = f64::from(u32::from_str_radix(&lexeme[2..], 16).expect("Should be valid hexadecimal"));
-                Ok(Literal(Value::Double(value)))
+                let value = f64::from(u32::from_str_radix(hex, 16).expect("Should be valid hexadecimal"));
+                Ok(self.expression_node(Literal(Value::Double(value))))
             } else {
                 let value = lexeme.parse().expect("Should be a valid f64");
-                Ok(Literal(Value::Double(value)))
+                Ok(self.expression_node(Literal(Value::Double(value))))
             };
         });
         match_token!(self, if var TokenKind::Identifier, {
-            return Ok(Variable(var));
+            return Ok(self.expression_node(Variable(var, Slot::Global)));
         });
         if self.match_token(TokenKind::LeftParen) {
+            let open = self.previous();
             let expr = self.expression()?;
-            self.consume(TokenKind::RightParen, "Unmatched delimiter: Expected `)`")?;
-            return Ok(Grouping(expr));
+            self.consume_closing(
+                TokenKind::RightParen,
+                &open,
+                "Unmatched delimiter: Expected `)`",
+            )?;
+            return Ok(self.expression_node(Grouping(expr)));
         }
 
         if self.match_token(TokenKind::LeftBrace) {
             let fields = self.consume_struct()?;
-            return Ok(Struct(fields));
+            return Ok(self.expression_node(Struct(fields)));
         }
 
         if self.match_token(TokenKind::LeftBracket) {
             let elements = self.consume_array()?;
-            return Ok(Array(elements));
+            return Ok(self.expression_node(Array(elements)));
         }
 
-        Err(IntError::Error {
-            message: "Expected Expression".into(),
-            token: self.tokens.get(self.current).cloned(),
-        })
+        Err(
+            IntError::new(self.tokens.get(self.current), "Expected Expression")
+                .with_code(Code::ExpectedExpression),
+        )
     }
 
-    fn consume_array(&mut self) -> Result<Vec<Expr>, IntError> {
+    fn consume_array(&mut self) -> Result<Vec<ExprId>, IntError> {
+        let open = self.previous();
         let mut elements = Vec::new();
 
         if let Some(token) = self.tokens.get(self.current) {
@@ -537,15 +588,17 @@ impl Parser {
             }
         }
 
-        self.consume(
+        self.consume_closing(
             TokenKind::RightBracket,
+            &open,
             "Unmatched delimiter: Expected `]` after array",
         )?;
 
         Ok(elements)
     }
 
-    fn consume_struct(&mut self) -> Result<Vec<(Token, Expr)>, IntError> {
+    fn consume_struct(&mut self) -> Result<Vec<(Token, ExprId)>, IntError> {
+        let open = self.previous();
         let mut fields = Vec::new();
 
         match_token!(self, while name TokenKind::Identifier, {
@@ -557,8 +610,9 @@ impl Parser {
             }
         });
 
-        self.consume(
+        self.consume_closing(
             TokenKind::RightBrace,
+            &open,
             "Unmatched delimiter: Expected `}` after struct",
         )?;
 
@@ -571,13 +625,29 @@ impl Parser {
             self.current += 1;
             Ok(token)
         } else {
-            Err(IntError::Error {
-                message: message.into(),
-                token: Some(token),
-            })
+            Err(IntError::new(Some(&token), message).with_code(Code::ExpectedToken))
         }
     }
 
+    // `consume` for the delimiter that closes `open`, an error also points at `open`
+    fn consume_closing(
+        &mut self,
+        kind: TokenKind,
+        open: &Token,
+        message: &str,
+    ) -> Result<Token, IntError> {
+        self.consume(kind, message).map_err(|mut err| {
+            if let IntError::Error { labels, .. } = &mut err {
+                labels.push(Label::new(open.span, "unclosed delimiter"));
+            }
+            err
+        })
+    }
+
+    fn previous(&self) -> Token {
+        self.tokens[self.current - 1].clone()
+    }
+
     fn is_at_end(&mut self) -> bool {
         matches!(
             self.tokens.get(self.current),
@@ -627,6 +697,14 @@ impl Parser {
         false
     }
 
+    fn expression_node(&mut self, expression: Expr) -> ExprId {
+        self.ast.add_expression(expression)
+    }
+
+    fn statement_node(&mut self, statement: Stmt) -> StmtId {
+        self.ast.add_statement(statement)
+    }
+
     pub fn lexeme(&self, token: &Token) -> &str {
         &self.source[token.span.start..token.span.end]
     }
@@ -1,6 +1,6 @@
 use std::ffi::{c_char, CString};
 
-use crate::{functions::IntCallable, value::Value, IntError};
+use crate::{functions::IntCallable, symbol::Symbol, value::Value, IntError};
 
 #[repr(u32)]
 #[allow(non_camel_case_types)]
@@ -138,7 +138,7 @@ impl IntCallable for InitWindow {
         }
         let width = arguments[0].double()? as i32;
         let height = arguments[1].double()? as i32;
-        let title = CString::new(arguments[2].get_string()?.borrow().as_str())
+        let title = CString::new(arguments[2].get_string()?)
             .expect("You shouldn't be able to construct a string with a nullbyte");
         unsafe {
             InitWindow(width, height, title.as_ptr());
@@ -267,7 +267,7 @@ impl IntCallable for DrawText {
         extern "C" {
             fn DrawText(text: *const c_char, posX: i32, posY: i32, fontSize: i32, color: u32);
         }
-        let text = CString::new(arguments[0].get_string()?.borrow().as_str())
+        let text = CString::new(arguments[0].get_string()?)
             .expect("You shouldn't be able to construct a string with a nullbyte");
         let pos_x = arguments[1].double()? as i32;
         let pos_y = arguments[2].double()? as i32;
@@ -472,16 +472,16 @@ impl IntCallable for CheckCollisionCircleRec {
         }
         let center = arguments[0].get_struct()?.borrow();
         let center = Vector2 {
-            x: center["x"].double()? as f32,
-            y: center["y"].double()? as f32,
+            x: center[&Symbol::intern("x")].double()? as f32,
+            y: center[&Symbol::intern("y")].double()? as f32,
         };
         let radius = arguments[1].double()? as f32;
         let rec = arguments[2].get_struct()?.borrow();
         let rec = Rectangle {
-            x: rec["x"].double()? as f32,
-            y: rec["y"].double()? as f32,
-            width: rec["width"].double()? as f32,
-            height: rec["height"].double()? as f32,
+            x: rec[&Symbol::intern("x")].double()? as f32,
+            y: rec[&Symbol::intern("y")].double()? as f32,
+            width: rec[&Symbol::intern("width")].double()? as f32,
+            height: rec[&Symbol::intern("height")].double()? as f32,
         };
         let result = unsafe { CheckCollisionCircleRec(center, radius, rec) };
         Ok(Value::Bool(result))
@@ -515,17 +515,17 @@ impl IntCallable for CheckCollisionRecs {
         }
         let rec1 = arguments[0].get_struct()?.borrow();
         let rec1 = Rectangle {
-            x: rec1["x"].double()? as f32,
-            y: rec1["y"].double()? as f32,
-            width: rec1["width"].double()? as f32,
-            height: rec1["height"].double()? as f32,
+            x: rec1[&Symbol::intern("x")].double()? as f32,
+            y: rec1[&Symbol::intern("y")].double()? as f32,
+            width: rec1[&Symbol::intern("width")].double()? as f32,
+            height: rec1[&Symbol::intern("height")].double()? as f32,
         };
         let rec2 = arguments[1].get_struct()?.borrow();
         let rec2 = Rectangle {
-            x: rec2["x"].double()? as f32,
-            y: rec2["y"].double()? as f32,
-            width: rec2["width"].double()? as f32,
-            height: rec2["height"].double()? as f32,
+            x: rec2[&Symbol::intern("x")].double()? as f32,
+            y: rec2[&Symbol::intern("y")].double()? as f32,
+            width: rec2[&Symbol::intern("width")].double()? as f32,
+            height: rec2[&Symbol::intern("height")].double()? as f32,
         };
         let result = unsafe { CheckCollisionRecs(rec1, rec2) };
         Ok(Value::Bool(result))
@@ -559,10 +559,10 @@ impl IntCallable for DrawRectangleRec {
         }
         let rec = arguments[0].get_struct()?.borrow();
         let rec = Rectangle {
-            x: rec["x"].double()? as f32,
-            y: rec["y"].double()? as f32,
-            width: rec["width"].double()? as f32,
-            height: rec["height"].double()? as f32,
+            x: rec[&Symbol::intern("x")].double()? as f32,
+            y: rec[&Symbol::intern("y")].double()? as f32,
+            width: rec[&Symbol::intern("width")].double()? as f32,
+            height: rec[&Symbol::intern("height")].double()? as f32,
         };
         let color = arguments[1].double()? as u32;
         unsafe {
@@ -0,0 +1,312 @@
+use ahash::AHashMap as HashMap;
+use std::mem;
+
+use crate::{
+    ast::{Ast, ExprId, Program, StmtId},
+    diagnostic::{Code, Diagnostic},
+    environment::Slot,
+    expression::Expr,
+    statement::Stmt,
+    suggest,
+    symbol::Symbol,
+    token::Token,
+};
+
+#[derive(Default)]
+struct Scope {
+    // the slot of every name, and whether its initializer finished running
+    names: HashMap<Symbol, (usize, bool)>,
+    len: usize,
+}
+
+/// Runs between parsing and execution: resolves every local variable to the slot it lives in and
+/// reports the errors that can be found without running the program.
+#[derive(Default)]
+pub struct Resolver {
+    scopes: Vec<Scope>,
+    in_function: bool,
+    loop_depth: usize,
+    errors: Vec<Diagnostic>,
+    // the slots are written once the whole program was walked, walking only reads the ast
+    statement_slots: Vec<(StmtId, Slot)>,
+    expression_slots: Vec<(ExprId, Slot)>,
+    suggestions: Vec<(Token, Vec<Symbol>)>,
+}
+
+impl Resolver {
+    pub fn resolve(mut self, program: &mut Program) -> Vec<Diagnostic> {
+        let ast = &mut program.ast;
+        for &statement in &program.statements {
+            self.statement(ast, statement);
+        }
+        for (id, slot) in self.statement_slots {
+            match &mut ast[id] {
+                Stmt::Var { slot: old, .. } | Stmt::Function { slot: old, .. } => *old = slot,
+                _ => unreachable!("only declarations have a slot"),
+            }
+        }
+        for (id, slot) in self.expression_slots {
+            match &mut ast[id] {
+                Expr::Variable { slot: old, .. } | Expr::Assign { slot: old, .. } => *old = slot,
+                _ => unreachable!("only variables have a slot"),
+            }
+        }
+        program.suggestions = self.suggestions;
+        self.errors
+    }
+
+    fn statement(&mut self, ast: &Ast, id: StmtId) {
+        match &ast[id] {
+            Stmt::Block { statements } => {
+                self.scopes.push(Scope::default());
+                for &statement in statements {
+                    self.statement(ast, statement);
+                }
+                self.scopes.pop();
+            }
+            Stmt::Expression { expression } | Stmt::Print { expression } => {
+                self.expression(ast, *expression)
+            }
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+            } => {
+                if let Some(initializer) = *initializer {
+                    self.statement(ast, initializer);
+                }
+                self.expression(ast, *condition);
+                if let Some(increment) = *increment {
+                    self.expression(ast, increment);
+                }
+                self.loop_body(ast, *body);
+            }
+            Stmt::While { condition, body } => {
+                self.expression(ast, *condition);
+                self.loop_body(ast, *body);
+            }
+            Stmt::Function {
+                name, params, body, ..
+            } => {
+                // functions only see their own locals and the globals, never the enclosing scopes
+                let slot = self.declare(name.name(), true);
+                self.statement_slots.push((id, slot));
+                let scopes = mem::replace(&mut self.scopes, vec![Scope::default()]);
+                let in_function = mem::replace(&mut self.in_function, true);
+                let loop_depth = mem::replace(&mut self.loop_depth, 0);
+                for param in params {
+                    // parameters always take the slot of their position, that's where the
+                    // arguments are
+                    let name = param.name();
+                    let scope = self.scopes.last_mut().expect("function scope");
+                    scope.names.insert(name, (scope.len, true));
+                    scope.len += 1;
+                }
+                for &statement in body {
+                    self.statement(ast, statement);
+                }
+                self.scopes = scopes;
+                self.in_function = in_function;
+                self.loop_depth = loop_depth;
+            }
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(ast, *condition);
+                self.statement(ast, *then_branch);
+                if let Some(else_branch) = *else_branch {
+                    self.statement(ast, else_branch);
+                }
+            }
+            Stmt::Return { keyword, value } => {
+                if !self.in_function {
+                    self.error(
+                        Code::TopLevelReturn,
+                        "Top level return is not allowed.",
+                        keyword,
+                    );
+                }
+                self.expression(ast, *value);
+            }
+            Stmt::Break { keyword } => {
+                if self.loop_depth == 0 {
+                    self.error(
+                        Code::BreakOutsideLoop,
+                        "break is only allowed in loops.",
+                        keyword,
+                    );
+                }
+            }
+            Stmt::Continue { keyword } => {
+                if self.loop_depth == 0 {
+                    self.error(
+                        Code::ContinueOutsideLoop,
+                        "continue is only allowed in loops.",
+                        keyword,
+                    );
+                }
+            }
+            Stmt::Var {
+                initializer, name, ..
+            } => {
+                let name = name.name();
+                self.declare(name, false);
+                self.expression(ast, *initializer);
+                let slot = self.declare(name, true);
+                self.statement_slots.push((id, slot));
+            }
+            Stmt::Append {
+                array, expression, ..
+            } => {
+                self.expression(ast, *array);
+                self.expression(ast, *expression);
+            }
+            Stmt::Insert {
+                array,
+                index,
+                expression,
+                ..
+            } => {
+                self.expression(ast, *array);
+                self.expression(ast, *index);
+                self.expression(ast, *expression);
+            }
+            Stmt::Delete { array, index, .. } => {
+                self.expression(ast, *array);
+                self.expression(ast, *index);
+            }
+        }
+    }
+
+    fn loop_body(&mut self, ast: &Ast, body: StmtId) {
+        self.loop_depth += 1;
+        self.statement(ast, body);
+        self.loop_depth -= 1;
+    }
+
+    fn expression(&mut self, ast: &Ast, id: ExprId) {
+        match &ast[id] {
+            Expr::Variable { name, .. } => {
+                let in_initializer = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.names.get(&name.name()))
+                    .is_some_and(|&(_, defined)| !defined);
+                if in_initializer {
+                    self.error(
+                        Code::OwnInitializer,
+                        "Can't read a local variable in its own initializer.",
+                        name,
+                    );
+                }
+                let slot = self.lookup(name);
+                self.expression_slots.push((id, slot));
+            }
+            Expr::Assign {
+                name, expression, ..
+            } => {
+                self.expression(ast, *expression);
+                let slot = self.lookup(name);
+                self.expression_slots.push((id, slot));
+            }
+            Expr::Unary { right, .. } => self.expression(ast, *right),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.expression(ast, *left);
+                self.expression(ast, *right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.expression(ast, *callee);
+                for &argument in arguments {
+                    self.expression(ast, argument);
+                }
+            }
+            Expr::Grouping { expression } => self.expression(ast, *expression),
+            Expr::Literal { .. } => {}
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(ast, *condition);
+                self.expression(ast, *then_branch);
+                self.expression(ast, *else_branch);
+            }
+            Expr::Struct { fields } => {
+                for &(_, value) in fields {
+                    self.expression(ast, value);
+                }
+            }
+            Expr::Array { elements } => {
+                for &element in elements {
+                    self.expression(ast, element);
+                }
+            }
+            Expr::IndexGet { array, index, .. } => {
+                self.expression(ast, *array);
+                self.expression(ast, *index);
+            }
+            Expr::IndexSet {
+                array,
+                index,
+                value,
+                ..
+            } => {
+                self.expression(ast, *array);
+                self.expression(ast, *index);
+                self.expression(ast, *value);
+            }
+            Expr::StructGet { target, .. } => self.expression(ast, *target),
+            Expr::StructSet { target, value, .. } => {
+                self.expression(ast, *target);
+                self.expression(ast, *value);
+            }
+        }
+    }
+
+    // a name declared again in the same scope keeps its slot, like assigning to it would
+    fn declare(&mut self, name: Symbol, defined: bool) -> Slot {
+        let Some(scope) = self.scopes.last_mut() else {
+            return Slot::Global;
+        };
+        let len = scope.len;
+        let (index, is_defined) = scope.names.entry(name).or_insert((len, defined));
+        if *index == len {
+            scope.len += 1;
+        }
+        *is_defined |= defined;
+        Slot::Local {
+            depth: 0,
+            index: *index,
+        }
+    }
+
+    fn lookup(&mut self, name: &Token) -> Slot {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if let Some(&(index, _)) = scope.names.get(&name.name()) {
+                return Slot::Local { depth, index };
+            }
+        }
+        // the locals are gone once the program runs, if the global turns out to be undefined
+        // the error can still suggest them
+        let locals = self
+            .scopes
+            .iter()
+            .flat_map(|scope| scope.names.keys().map(|local| local.as_str()));
+        let close = suggest::closest(name.name().as_str(), locals);
+        if !close.is_empty() {
+            let close = close.into_iter().map(Symbol::from).collect();
+            self.suggestions.push((name.clone(), close));
+        }
+        Slot::Global
+    }
+
+    fn error(&mut self, code: Code, message: &str, token: &Token) {
+        self.errors
+            .push(Diagnostic::error(code, message).at(Some(token)));
+    }
+}
@@ -1,10 +1,16 @@
-use std::process::exit;
+use unicode_ident::{is_xid_continue, is_xid_start};
 
-use crate::token::{Token, TokenKind};
+use crate::{
+    diagnostic::{Code, Diagnostic},
+    symbol::Symbol,
+    token::{Span, Token, TokenKind},
+};
 
 pub struct Scanner {
     pub source: String,
     pub tokens: Vec<Token>,
+    /// The errors in the source, the scanner keeps going after them.
+    pub diagnostics: Vec<Diagnostic>,
     start: usize,
     current: usize,
     line: usize,
@@ -15,18 +21,37 @@ impl Scanner {
         Self {
             source,
             tokens: Vec::new(),
+            diagnostics: Vec::new(),
             start: 0,
             current: 0,
             line: 1,
         }
     }
+
+    /// Only scans the source from byte `start` on, the spans of the tokens are still offsets into
+    /// the whole source.
+    pub fn starting_at(source: String, start: usize) -> Self {
+        Self {
+            start,
+            current: start,
+            ..Self::new(source)
+        }
+    }
+
     pub fn scan(&mut self) {
         while !self.is_at_end() {
             self.start = self.current;
             self.scan_token();
         }
 
-        self.tokens.push(Token::eof(self.line));
+        // errors at the end point right after the last token
+        let end = self
+            .tokens
+            .last()
+            .map_or(self.current, |token| token.span.end);
+        let mut eof = Token::eof(self.line);
+        eof.span = Span::from((end, end));
+        self.tokens.push(eof);
     }
 
     fn scan_token(&mut self) {
@@ -91,8 +116,14 @@ impl Scanner {
                     }
 
                     if !self.try_consume(b'*') || !self.try_consume(b'/') {
-                        println!("Unterminated block comment at line {}.", self.line);
-                        exit(1);
+                        // there is nothing after the comment to turn into a token
+                        self.diagnostics.push(
+                            Diagnostic::error(
+                                Code::UnterminatedComment,
+                                "Unterminated block comment.",
+                            )
+                            .with_span((self.start, self.start + 2).into()),
+                        );
                     }
                 } else {
                     self.add_token(Slash);
@@ -101,16 +132,34 @@ impl Scanner {
             b' ' | b'\r' | b'\t' => {}
             b'\n' => self.line += 1,
             b'"' => self.consume_string_literal(),
+            b'r' if matches!(self.peek(), Some(b'"' | b'#')) => self.consume_raw_string_literal(),
             c if c.is_ascii_digit() => self.consume_number_literal(),
-            c if c.is_ascii_alphabetic() => self.consume_identifer(),
-            _ => {
-                // TODO: better error handling
-                println!("Unexpected Character at line {}", self.line);
-                exit(1);
-            }
+            c if c == b'_' || c.is_ascii_alphabetic() => self.consume_identifer(),
+            _ => match self.char_at(self.start) {
+                Some(c) if is_xid_start(c) => {
+                    self.current = self.start + c.len_utf8();
+                    self.consume_identifer();
+                }
+                c => {
+                    self.current = self.start + c.map_or(1, char::len_utf8);
+                    self.error(
+                        Code::UnexpectedCharacter,
+                        "Unexpected character.",
+                        self.current - self.start,
+                    );
+                }
+            },
         };
     }
 
+    // the current token becomes an error token, the diagnostic points at its first `len` bytes
+    fn error(&mut self, code: Code, message: &str, len: usize) {
+        self.diagnostics.push(
+            Diagnostic::error(code, message).with_span((self.start, self.start + len).into()),
+        );
+        self.add_token(TokenKind::Error);
+    }
+
     fn consume_hex_literal(&mut self) {
         while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
             self.consume();
@@ -120,14 +169,19 @@ impl Scanner {
     }
 
     fn consume_identifer(&mut self) {
-        while self
-            .peek()
-            .is_some_and(|c| c.is_ascii_alphanumeric() || c == b'_')
-        {
-            self.consume();
+        while let Some(c) = self.char_at(self.current).filter(|&c| is_xid_continue(c)) {
+            self.current += c.len_utf8();
+        }
+        let text = &self.source[self.start..self.current];
+        match Self::get_keyword(text) {
+            Some(keyword) => self.add_token(keyword),
+            None => {
+                let mut token =
+                    Token::new(TokenKind::Identifier, (self.start, self.current), self.line);
+                token.symbol = Some(Symbol::intern(text));
+                self.tokens.push(token);
+            }
         }
-        let text = self.source[self.start..self.current].to_string();
-        self.add_token(Self::get_keyword(&text).unwrap_or(TokenKind::Identifier));
     }
 
     fn consume_number_literal(&mut self) {
@@ -153,20 +207,53 @@ impl Scanner {
     }
 
     fn consume_string_literal(&mut self) {
-        while self.peek().is_some_and(|c| c != b'"') {
-            if self.peek() == Some(b'\n') {
-                self.line += 1;
-            }
-            self.consume();
+        let terminator = if self.source[self.current..].starts_with("\"\"") {
+            self.current += 2;
+            "\"\"\""
+        } else {
+            "\""
+        };
+
+        if self.consume_until(terminator, true) {
+            self.add_token(TokenKind::String);
+        } else {
+            self.error(Code::UnterminatedString, "Unterminated string.", 1);
         }
+    }
 
-        if !self.try_consume(b'"') {
-            // TODO: better error handling
-            println!("Unterminated String at line {}", self.line);
-            exit(1);
+    // r"..." or r#"..."#, with as many `#` as needed to allow `"#` inside the string
+    fn consume_raw_string_literal(&mut self) {
+        let mut hashes = 0;
+        while self.try_consume(b'#') {
+            hashes += 1;
         }
 
-        self.add_token(TokenKind::String);
+        if !self.try_consume(b'"')
+            || !self.consume_until(&format!("\"{}", "#".repeat(hashes)), false)
+        {
+            self.error(Code::UnterminatedString, "Unterminated string.", 1);
+        } else {
+            self.add_token(TokenKind::String);
+        }
+    }
+
+    // consumes everything up to and including `terminator`, returns false if the source ended
+    // first. with `escapes` a `\` also consumes the character after it.
+    fn consume_until(&mut self, terminator: &str, escapes: bool) -> bool {
+        while !self.is_at_end() {
+            if self.source.as_bytes()[self.current..].starts_with(terminator.as_bytes()) {
+                self.current += terminator.len();
+                return true;
+            }
+            let mut char = self.consume();
+            if char == b'\\' && escapes && !self.is_at_end() {
+                char = self.consume();
+            }
+            if char == b'\n' {
+                self.line += 1;
+            }
+        }
+        false
     }
 
     fn consume(&mut self) -> u8 {
@@ -175,6 +262,10 @@ impl Scanner {
         char
     }
 
+    fn char_at(&self, index: usize) -> Option<char> {
+        self.source.get(index..)?.chars().next()
+    }
+
     fn peek(&self) -> Option<u8> {
         self.source.as_bytes().get(self.current).copied()
     }
@@ -211,7 +302,9 @@ impl Scanner {
             "for" => Some(For),
             "fun" => Some(Fun),
             "if" => Some(If),
+            "is" => Some(Is),
             "nil" => Some(Nil),
+            "not" => Some(Not),
             "or" => Some(Or),
             "print" => Some(Print),
             "return" => Some(Return),
@@ -229,3 +322,108 @@ impl Scanner {
         }
     }
 }
+
+/// The value of a string literal. Raw strings are taken as they are, the others get their escape
+/// sequences processed, and triple quoted strings also lose the indentation their lines share.
+/// An escape sequence that isn't one is kept as it's written, the messages say which.
+pub fn string_literal_value(lexeme: &str) -> (String, Vec<String>) {
+    if let Some(raw) = lexeme.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return (
+            raw[hashes + 1..raw.len() - hashes - 1].to_string(),
+            Vec::new(),
+        );
+    }
+    if let Some(text) = lexeme
+        .strip_prefix("\"\"\"")
+        .and_then(|text| text.strip_suffix("\"\"\""))
+    {
+        return unescape(&dedent(text));
+    }
+    unescape(&lexeme[1..lexeme.len() - 1])
+}
+
+// strings used to be taken as they are except for `\n`, so unknown escapes still are
+fn unescape(text: &str) -> (String, Vec<String>) {
+    let mut result = String::with_capacity(text.len());
+    let mut warnings = Vec::new();
+    let mut rest = text;
+    while let Some(backslash) = rest.find('\\') {
+        result.push_str(&rest[..backslash]);
+        rest = &rest[backslash + 1..];
+        let mut chars = rest.chars();
+        let escaped = match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('u') => {
+                let code = chars
+                    .as_str()
+                    .strip_prefix('{')
+                    .and_then(|digits| digits.split_once('}'))
+                    .and_then(|(digits, after)| {
+                        let code = u32::from_str_radix(digits, 16).ok()?;
+                        Some((char::from_u32(code)?, after))
+                    });
+                match code {
+                    Some((code, after)) => {
+                        result.push(code);
+                        rest = after;
+                    }
+                    None => {
+                        warnings.push(
+                            "Invalid unicode escape, expected `\\u{...}`, the `\\u` is kept as it is."
+                                .to_string(),
+                        );
+                        result.push('\\');
+                    }
+                }
+                continue;
+            }
+            Some(char) => {
+                warnings.push(format!(
+                    "Unknown escape sequence `\\{char}`, it's kept as it is."
+                ));
+                result.push('\\');
+                continue;
+            }
+            None => {
+                warnings.push("Unfinished escape sequence, the `\\` is kept as it is.".into());
+                result.push('\\');
+                continue;
+            }
+        };
+        result.push(escaped);
+        rest = chars.as_str();
+    }
+    result.push_str(rest);
+    (result, warnings)
+}
+
+// a line break right after the opening quotes and the line holding the closing quotes aren't
+// part of the string, and the indentation shared by the remaining lines is removed.
+fn dedent(text: &str) -> String {
+    let text = text
+        .strip_prefix('\n')
+        .or_else(|| text.strip_prefix("\r\n"))
+        .unwrap_or(text);
+    let text = match text.rfind('\n') {
+        Some(end) if text[end..].trim().is_empty() => text[..end].trim_end_matches('\r'),
+        _ => text,
+    };
+
+    let indent = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    text.lines()
+        .map(|line| line.get(indent..).unwrap_or(line.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
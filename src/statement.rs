@@ -1,34 +1,43 @@
 #![allow(non_snake_case)]
-use crate::{expression::Expr, functions::Function, generate_enum_and_functions, token::Token};
+use crate::{
+    ast::{ExprId, StmtId},
+    environment::Slot,
+    generate_enum_and_functions,
+    token::Token,
+};
 
 generate_enum_and_functions! {
     Stmt {
         Block {
-            statements: Vec<Stmt>
+            statements: Vec<StmtId>
         },
         Expression {
-            expression: Expr,
+            expression: ExprId,
         },
         For {
-            initializer: Option<Stmt>,
-            condition: Expr,
-            increment: Option<Expr>,
-            body: Stmt,
+            initializer: Option<StmtId>,
+            condition: ExprId,
+            increment: Option<ExprId>,
+            body: StmtId,
         },
+        // the body is shared with every function value made from it, see `functions::Function`
         Function {
-            fun: Function,
+            name: Token,
+            params: Vec<Token>,
+            body: Vec<StmtId>,
+            slot: Slot,
         },
         If {
-            condition: Expr,
-            then_branch: Stmt,
-            else_branch: Option<Stmt>,
+            condition: ExprId,
+            then_branch: StmtId,
+            else_branch: Option<StmtId>,
         },
         Print {
-            expression: Expr,
+            expression: ExprId,
         },
         Return {
             keyword: Token,
-            value: Expr,
+            value: ExprId,
         },
         Break {
             keyword: Token,
@@ -37,28 +46,29 @@ generate_enum_and_functions! {
             keyword: Token,
         },
         Var {
-            initializer: Expr,
+            initializer: ExprId,
             name: Token,
+            slot: Slot,
         },
         While {
-            condition: Expr,
-            body: Stmt,
+            condition: ExprId,
+            body: StmtId,
         },
         Append {
             paren: Token,
-            array: Expr,
-            expression: Expr,
+            array: ExprId,
+            expression: ExprId,
         },
         Insert {
             paren: Token,
-            array: Expr,
-            index: Expr,
-            expression: Expr,
+            array: ExprId,
+            index: ExprId,
+            expression: ExprId,
         },
         Delete {
             paren: Token,
-            array: Expr,
-            index: Expr,
+            array: ExprId,
+            index: ExprId,
         }
     }
 }
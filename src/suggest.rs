@@ -0,0 +1,64 @@
+const MAX_SUGGESTIONS: usize = 3;
+// any name of one or two characters is an edit or two away from any other
+const MIN_LENGTH: usize = 3;
+
+/// The names in `candidates` that `name` is probably a typo of, closest first. A candidate
+/// qualifies when it's at most a third of the name's length of edits away, and at least one.
+/// Names shorter than three characters get no suggestions.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let length = name.chars().count();
+    if length < MIN_LENGTH {
+        return Vec::new();
+    }
+    let max_distance = (length / 3).max(1);
+    let mut close: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|&candidate| candidate != name)
+        // the distance is at least the difference in length
+        .filter(|candidate| candidate.chars().count().abs_diff(length) <= max_distance)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .collect();
+    close.sort_unstable();
+    close.dedup();
+    close.truncate(MAX_SUGGESTIONS);
+    close.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+/// " Did you mean `a`?" or " Did you mean `a`, `b` or `c`?", nothing without names. Made to be
+/// appended to an error message.
+pub fn did_you_mean(names: &[&str]) -> String {
+    match names {
+        [] => String::new(),
+        [name] => format!(" Did you mean `{name}`?"),
+        [first @ .., last] => {
+            let first: Vec<String> = first.iter().map(|name| format!("`{name}`")).collect();
+            format!(" Did you mean {} or `{last}`?", first.join(", "))
+        }
+    }
+}
+
+/// The number of characters to insert, delete, replace or swap with their neighbour to turn `a`
+/// into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // three rows of the table, swaps look two characters back
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        (before, previous, current) = (previous, current, before);
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,65 @@
+use ahash::AHashMap as HashMap;
+use std::{
+    cell::RefCell,
+    fmt::{Debug, Display},
+};
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::default());
+}
+
+// interned names live as long as the program, so they're leaked and handed out as `&'static str`
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+/// An interned name. Identifiers get one when they're scanned, so environments and struct maps
+/// compare and hash integers instead of strings. Symbols are never freed, so only names from the
+/// source are interned, not strings made at runtime.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn intern(name: &str) -> Symbol {
+        INTERNER.with_borrow_mut(|interner| {
+            if let Some(&symbol) = interner.symbols.get(name) {
+                return symbol;
+            }
+            let symbol = Symbol(interner.names.len() as u32);
+            let name: &'static str = Box::leak(name.into());
+            interner.names.push(name);
+            interner.symbols.insert(name, symbol);
+            symbol
+        })
+    }
+
+    /// The symbol of `name` if it was ever interned. Lookups with arbitrary strings use this, a
+    /// name that was never interned can't be a key of anything.
+    pub fn lookup(name: &str) -> Option<Symbol> {
+        INTERNER.with_borrow(|interner| interner.symbols.get(name).copied())
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.with_borrow(|interner| interner.names[self.0 as usize])
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt::Debug;
 
+use crate::symbol::Symbol;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenKind {
     LeftParen,
@@ -27,6 +29,7 @@ pub enum TokenKind {
     GreaterEqual,
     Less,
     LessEqual,
+    IsNot,
 
     // Literals.
     Identifier,
@@ -41,7 +44,9 @@ pub enum TokenKind {
     Fun,
     For,
     If,
+    Is,
     Nil,
+    Not,
     Or,
     Print,
     Return,
@@ -56,6 +61,8 @@ pub enum TokenKind {
     Insert,
     Delete,
 
+    /// Source the scanner couldn't read, it already reported why.
+    Error,
     Eof,
 }
 
@@ -76,6 +83,8 @@ pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
     pub line: usize,
+    /// The interned name of an identifier.
+    pub symbol: Option<Symbol>,
 }
 
 impl Token {
@@ -84,6 +93,7 @@ impl Token {
             kind: TokenKind::Eof,
             span: Span::default(),
             line,
+            symbol: None,
         }
     }
     pub fn new(kind: TokenKind, span: impl Into<Span>, line: usize) -> Self {
@@ -91,8 +101,14 @@ impl Token {
             kind,
             span: span.into(),
             line,
+            symbol: None,
         }
     }
+
+    /// The name of an identifier token.
+    pub fn name(&self) -> Symbol {
+        self.symbol.expect("only identifiers have a name")
+    }
 }
 
 impl TokenKind {
@@ -100,3 +116,9 @@ impl TokenKind {
         std::mem::discriminant(self) == std::mem::discriminant(other)
     }
 }
+
+impl AsRef<Token> for Token {
+    fn as_ref(&self) -> &Token {
+        self
+    }
+}
@@ -1,8 +1,184 @@
-use ahash::AHashMap as HashMap;
+use ahash::{AHashMap as HashMap, AHashSet as HashSet, RandomState};
+use indexmap::{Equivalent, IndexMap};
 use std::fmt::Debug;
-use std::{cell::RefCell, fmt::Display, rc::Rc};
+use std::{
+    cell::RefCell,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    iter::once,
+    mem::{self, size_of},
+    ops::{Index, Range},
+    rc::Rc,
+};
 
-use crate::functions::IntCallable;
+use crate::{diagnostic::Code, functions::IntCallable, gc, memory, symbol::Symbol, IntError};
+
+/// A field name. Names from the source are symbols, names made at runtime from strings stay
+/// refcounted so they're freed with their struct instead of being interned for good.
+#[derive(Debug, Clone)]
+pub enum Key {
+    Symbol(Symbol),
+    Name(Rc<str>),
+}
+
+impl Key {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Key::Symbol(symbol) => symbol.as_str(),
+            Key::Name(name) => name,
+        }
+    }
+
+    fn query(&self) -> Query<'_> {
+        match self {
+            Key::Symbol(symbol) => Query::Symbol(*symbol),
+            Key::Name(name) => Query::Name(name),
+        }
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.query() == other.query()
+    }
+}
+
+impl Eq for Key {}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.query().hash(state);
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// looks up a key without allocating a name
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Query<'a> {
+    Symbol(Symbol),
+    Name(&'a str),
+}
+
+impl Equivalent<Key> for Query<'_> {
+    fn equivalent(&self, key: &Key) -> bool {
+        *self == key.query()
+    }
+}
+
+/// Struct fields, kept in insertion order so printing and iterating a struct is deterministic.
+///
+/// A name made at runtime is keyed by its symbol if it has one. One that was only interned after
+/// its field was added keeps its `Key::Name`, so a lookup that misses the symbol tries the name.
+#[derive(Debug, Clone, Default)]
+pub struct StructMap {
+    fields: IndexMap<Key, Value, RandomState>,
+    // the bytes taken by the names of the `Key::Name` fields
+    name_bytes: usize,
+}
+
+impl StructMap {
+    pub fn get(&self, name: Symbol) -> Option<&Value> {
+        self.index_of(name).map(|i| &self.fields[i])
+    }
+
+    /// `get` with a name made at runtime.
+    pub fn get_name(&self, name: &str) -> Option<&Value> {
+        match Symbol::lookup(name) {
+            Some(symbol) => self.get(symbol),
+            None => self.fields.get(&Query::Name(name)),
+        }
+    }
+
+    /// Sets a field and returns its old value, or `None` if the field is new.
+    pub fn insert(&mut self, name: Symbol, value: Value) -> Option<Value> {
+        match self.index_of(name) {
+            Some(i) => Some(mem::replace(&mut self.fields[i], value)),
+            None => self.push(Key::Symbol(name), value),
+        }
+    }
+
+    /// `insert` with a name made at runtime.
+    pub fn insert_name(&mut self, name: &str, value: Value) -> Option<Value> {
+        if let Some(symbol) = Symbol::lookup(name) {
+            return self.insert(symbol, value);
+        }
+        match self.fields.get_mut(&Query::Name(name)) {
+            Some(old) => Some(mem::replace(old, value)),
+            None => self.push(Key::Name(name.into()), value),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Key> {
+        self.fields.keys()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.fields.values()
+    }
+
+    pub fn into_values(self) -> impl Iterator<Item = Value> {
+        self.fields.into_values()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Value)> {
+        self.fields.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.fields.capacity()
+    }
+
+    /// The bytes taken by the names that were made at runtime, the fields themselves aren't
+    /// included.
+    pub fn name_bytes(&self) -> usize {
+        self.name_bytes
+    }
+
+    fn get_key(&self, key: &Key) -> Option<&Value> {
+        match key {
+            Key::Symbol(symbol) => self.get(*symbol),
+            Key::Name(name) => self.get_name(name),
+        }
+    }
+
+    fn index_of(&self, name: Symbol) -> Option<usize> {
+        self.fields
+            .get_index_of(&Query::Symbol(name))
+            .or_else(|| match self.name_bytes {
+                0 => None,
+                _ => self.fields.get_index_of(&Query::Name(name.as_str())),
+            })
+    }
+
+    fn push(&mut self, key: Key, value: Value) -> Option<Value> {
+        if let Key::Name(name) = &key {
+            // the name and the counts in front of it
+            self.name_bytes += name.len() + 2 * size_of::<usize>();
+        }
+        self.fields.insert(key, value)
+    }
+}
+
+impl Index<&Symbol> for StructMap {
+    type Output = Value;
+
+    fn index(&self, name: &Symbol) -> &Value {
+        self.get(*name).expect("the struct has no such field")
+    }
+}
 
 #[derive(Clone)]
 pub struct Fun(pub Rc<dyn IntCallable>);
@@ -17,63 +193,156 @@ impl Debug for Fun {
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub enum Object {
-    String(Rc<RefCell<String>>),
-    Struct(Rc<RefCell<HashMap<String, Value>>>),
+    /// Strings are values, an `Rc` is only shared until someone modifies it.
+    String(Rc<String>),
+    /// A mutable string that is appended to in place, for building strings in loops.
+    Builder(Rc<RefCell<String>>),
+    Struct(Rc<RefCell<StructMap>>),
     Array(Rc<RefCell<Vec<Value>>>),
 }
 
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut HashSet::new())
+    }
+}
+
 impl Object {
-    pub fn deep_clone(&self) -> Object {
+    /// Identity comparison, true only when both objects share the same allocation. Strings are
+    /// values whose allocation is only shared until one is modified, so they compare by value.
+    pub fn is(&self, other: &Object) -> bool {
+        match (self, other) {
+            (Object::String(a), Object::String(b)) => a == b,
+            (Object::Builder(a), Object::Builder(b)) => Rc::ptr_eq(a, b),
+            (Object::Struct(a), Object::Struct(b)) => Rc::ptr_eq(a, b),
+            (Object::Array(a), Object::Array(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn addr(&self) -> usize {
         match self {
-            Object::String(string) => {
-                Object::String(Rc::new(RefCell::new(string.borrow().clone())))
+            Object::String(string) => Rc::as_ptr(string) as usize,
+            Object::Builder(builder) => Rc::as_ptr(builder) as usize,
+            Object::Struct(map) => Rc::as_ptr(map) as usize,
+            Object::Array(array) => Rc::as_ptr(array) as usize,
+        }
+    }
+
+    // structural equality. `seen` holds the pairs that are already being compared further up,
+    // assuming them equal is what makes comparing self-referential objects terminate.
+    fn equals(&self, other: &Object, seen: &mut HashSet<(usize, usize)>) -> bool {
+        if self.is(other) || !seen.insert((self.addr(), other.addr())) {
+            return true;
+        }
+        match (self, other) {
+            (Object::String(a), Object::String(b)) => a == b,
+            (Object::Builder(a), Object::Builder(b)) => *a.borrow() == *b.borrow(),
+            (Object::Struct(a), Object::Struct(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(key, value)| b.get_key(key).is_some_and(|v| value.equals(v, seen)))
             }
-            Object::Struct(map) => {
-                let mut map_clone = map.borrow().clone();
-                map_clone.values_mut().for_each(|v| *v = v.deep_clone());
-                Object::Struct(Rc::new(RefCell::new(map_clone)))
+            (Object::Array(a), Object::Array(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.equals(b, seen))
             }
-            Object::Array(array) => {
-                let mut map_array = array.borrow().clone();
-                map_array.iter_mut().for_each(|v| *v = v.deep_clone());
-                Object::Array(Rc::new(RefCell::new(map_array)))
+            _ => false,
+        }
+    }
+
+    pub fn deep_clone(&self) -> Object {
+        self.deep_clone_with(&mut HashMap::new())
+    }
+
+    // `copies` maps every object that was already copied to its copy, so objects that are shared
+    // stay shared in the clone and cycles point back into the new graph instead of recursing forever.
+    fn deep_clone_with(&self, copies: &mut HashMap<usize, Object>) -> Object {
+        if let Some(copy) = copies.get(&self.addr()) {
+            return copy.clone();
+        }
+        let copy = match self {
+            Object::String(_) => return self.clone(),
+            Object::Builder(builder) => {
+                Object::Builder(Rc::new(RefCell::new(builder.borrow().clone())))
+            }
+            Object::Struct(_) => Object::Struct(Rc::default()),
+            Object::Array(_) => Object::Array(Rc::default()),
+        };
+        copies.insert(self.addr(), copy.clone());
+        match (self, &copy) {
+            (Object::Struct(map), Object::Struct(map_clone)) => {
+                for (key, value) in map.borrow().iter() {
+                    let value = value.deep_clone_with(copies);
+                    map_clone.borrow_mut().push(key.clone(), value);
+                }
+            }
+            (Object::Array(array), Object::Array(array_clone)) => {
+                for value in array.borrow().iter() {
+                    let value = value.deep_clone_with(copies);
+                    array_clone.borrow_mut().push(value);
+                }
             }
+            _ => {}
         }
+        // tracked once it's filled so its contents are counted
+        gc::track(&copy);
+        memory::track(&copy);
+        copy
     }
-}
 
-impl Display for Object {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // `parents` holds the objects that are currently being printed, running into one of them
+    // again means the object contains itself, so it's printed as `[...]` or `{...}`.
+    fn fmt_nested(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        parents: &mut Vec<usize>,
+    ) -> std::fmt::Result {
         match self {
-            Object::String(string) => std::fmt::Display::fmt(string.borrow().as_str(), f),
+            Object::String(string) => std::fmt::Display::fmt(string.as_str(), f),
+            Object::Builder(builder) => std::fmt::Display::fmt(builder.borrow().as_str(), f),
+            Object::Struct(_) if parents.contains(&self.addr()) => write!(f, "{{...}}"),
+            Object::Array(_) if parents.contains(&self.addr()) => write!(f, "[...]"),
             Object::Struct(map) => {
-                let mut fields = map
-                    .borrow()
-                    .iter()
-                    .map(|(k, v)| format!("{k}: {v}"))
-                    .collect::<Vec<String>>()
-                    .join(", ");
-                if !fields.is_empty() {
-                    fields = String::from(" ") + &fields + " ";
+                parents.push(self.addr());
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    write!(f, "{} {key}: ", if i == 0 { "" } else { "," })?;
+                    value.fmt_nested(f, parents)?;
+                }
+                parents.pop();
+                if map.borrow().is_empty() {
+                    write!(f, "}}")
+                } else {
+                    write!(f, " }}")
                 }
-                write!(f, "{{{fields}}}")
             }
             Object::Array(array) => {
-                let elements = array
-                    .borrow()
-                    .iter()
-                    .map(ToString::to_string)
-                    .collect::<Vec<String>>()
-                    .join(", ");
-                write!(f, "[{elements}]")
+                parents.push(self.addr());
+                write!(f, "[")?;
+                for (i, value) in array.borrow().iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    value.fmt_nested(f, parents)?;
+                }
+                parents.pop();
+                write!(f, "]")
             }
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Display for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_nested(f, &mut Vec::new())
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Value {
     Double(f64),
     Bool(bool),
@@ -94,38 +363,89 @@ impl Display for Value {
     }
 }
 
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut HashSet::new())
+    }
+}
+
 impl Value {
+    /// Identity comparison used by `is`: objects other than strings and functions are identical
+    /// only when they are the same allocation, everything else compares by value.
+    pub fn is(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Object(a), Value::Object(b)) => a.is(b),
+            (a, b) => a == b,
+        }
+    }
+
+    fn equals(&self, other: &Value, seen: &mut HashSet<(usize, usize)>) -> bool {
+        match (self, other) {
+            (Value::Double(a), Value::Double(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Fun(a), Value::Fun(b)) => a == b,
+            (Value::Object(a), Value::Object(b)) => a.equals(b, seen),
+            _ => false,
+        }
+    }
+
     pub fn deep_clone(&self) -> Value {
-        let clone = match self {
-            Value::Object(object) => Value::Object(object.deep_clone()),
+        self.deep_clone_with(&mut HashMap::new())
+    }
+
+    fn deep_clone_with(&self, copies: &mut HashMap<usize, Object>) -> Value {
+        match self {
+            Value::Object(object) => Value::Object(object.deep_clone_with(copies)),
             value => value.clone(),
-        };
-        clone
+        }
+    }
+
+    fn fmt_nested(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        parents: &mut Vec<usize>,
+    ) -> std::fmt::Result {
+        match self {
+            Value::Object(object) => object.fmt_nested(f, parents),
+            value => std::fmt::Display::fmt(value, f),
+        }
     }
 
     pub fn new_fun(fun: impl IntCallable + 'static) -> Value {
         Value::Fun(Fun(Rc::new(fun)))
     }
 
-    pub fn new_struct(structure: HashMap<String, Value>) -> Value {
-        Value::Object(Object::Struct(Rc::new(RefCell::new(structure))))
+    pub fn new_struct(structure: StructMap) -> Value {
+        let object = Object::Struct(Rc::new(RefCell::new(structure)));
+        gc::track(&object);
+        memory::track(&object);
+        Value::Object(object)
     }
 
     pub fn new_string(string: String) -> Value {
-        Value::Object(Object::String(Rc::new(RefCell::new(
-            // FIX: temp & dumb, do actual unescaping
-            string.replace("\\n", "\n"),
-        ))))
+        let object = Object::String(Rc::new(string));
+        memory::track(&object);
+        Value::Object(object)
+    }
+
+    pub fn new_builder(string: String) -> Value {
+        let object = Object::Builder(Rc::new(RefCell::new(string)));
+        memory::track(&object);
+        Value::Object(object)
     }
 
     pub fn new_array(array: Vec<Value>) -> Value {
-        Value::Object(Object::Array(Rc::new(RefCell::new(array))))
+        let object = Object::Array(Rc::new(RefCell::new(array)));
+        gc::track(&object);
+        memory::track(&object);
+        Value::Object(object)
     }
 
-    pub fn double(&self) -> Result<f64, String> {
+    pub fn double(&self) -> Result<f64, TypeMismatch> {
         match self {
             Value::Double(value) => Ok(*value),
-            _ => Err("Operand must be a number".into()),
+            _ => Err(TypeMismatch("a number")),
         }
     }
 
@@ -133,31 +453,65 @@ impl Value {
         !matches!(self, Value::Bool(false) | Value::Nil)
     }
 
-    pub fn get_fun(self) -> Result<Fun, String> {
+    pub fn get_fun(self) -> Result<Fun, TypeMismatch> {
         match self {
             Value::Fun(f) => Ok(f),
-            _ => Err("Operand must be a function".into()),
+            _ => Err(TypeMismatch("a function")),
         }
     }
 
-    pub fn get_array(&self) -> Result<&RefCell<Vec<Value>>, String> {
+    pub fn get_array(&self) -> Result<&RefCell<Vec<Value>>, TypeMismatch> {
         match self {
             Value::Object(Object::Array(array)) => Ok(array),
-            _ => Err("Operand must be an array".into()),
+            _ => Err(TypeMismatch("an array")),
         }
     }
 
-    pub fn get_struct(&self) -> Result<&RefCell<HashMap<String, Value>>, String> {
+    pub fn get_struct(&self) -> Result<&RefCell<StructMap>, TypeMismatch> {
         match self {
             Value::Object(Object::Struct(map)) => Ok(map),
-            _ => Err("Operand must be a struct".into()),
+            _ => Err(TypeMismatch("a struct")),
         }
     }
 
-    pub fn get_string(&self) -> Result<&RefCell<String>, String> {
+    pub fn get_string(&self) -> Result<&str, TypeMismatch> {
         match self {
             Value::Object(Object::String(string)) => Ok(string),
-            _ => Err("Operand must be a string".into()),
+            _ => Err(TypeMismatch("a string")),
         }
     }
 }
+
+/// A value of the wrong type, like a string where a number is needed. Holds what was expected.
+#[derive(Debug)]
+pub struct TypeMismatch(&'static str);
+
+impl Display for TypeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Operand must be {}", self.0)
+    }
+}
+
+impl From<TypeMismatch> for IntError {
+    fn from(err: TypeMismatch) -> Self {
+        IntError::new(None, err.to_string()).with_code(Code::TypeMismatch)
+    }
+}
+
+/// Strings are indexed by characters, this is the byte range of the characters `start..end` of
+/// `string`, or `None` if they're out of bounds.
+pub fn char_range(string: &str, start: usize, end: usize) -> Option<Range<usize>> {
+    if start > end {
+        return None;
+    }
+    let mut boundaries = string
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(once(string.len()));
+    let byte_start = boundaries.nth(start)?;
+    let byte_end = match end - start {
+        0 => byte_start,
+        len => boundaries.nth(len - 1)?,
+    };
+    Some(byte_start..byte_end)
+}
@@ -0,0 +1,350 @@
+use std::{mem, rc::Rc};
+
+use crate::{
+    chunk::{Chunk, Op, Place},
+    interpreter::{
+        add_to_variable, append, append_to_variable, arity_error, binary, delete, index_get,
+        index_set, insert, missing_field, unary, unwritable_string, Interpreter,
+    },
+    memory::{self, FIELD_SIZE},
+    token::Token,
+    value::{StructMap, Value},
+    IntError, WithToken,
+};
+
+struct Frame {
+    chunk: Rc<Chunk>,
+    ip: usize,
+    // the stack slot of the first argument
+    base: usize,
+}
+
+impl Frame {
+    // the token of the instruction that is running
+    fn token(&self) -> &Token {
+        &self.chunk.tokens[self.ip - 1]
+    }
+}
+
+/// Runs bytecode produced by the `Compiler`. Globals and native functions are the interpreter's,
+/// calls between compiled functions stay in the vm's loop while natives are called through
+/// `IntCallable` like in the tree walker.
+pub struct Vm<'a> {
+    interpreter: &'a mut Interpreter,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Self {
+            interpreter,
+            stack: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Runs `chunk` with `arguments` in its first slots and returns what it returned.
+    pub fn run(mut self, chunk: Rc<Chunk>, arguments: Vec<Value>) -> Result<Value, IntError> {
+        // the frames that are still active when an error ends the run never return
+        let call_depth = self.interpreter.call_depth();
+        let result = self
+            .execute(chunk, arguments)
+            .map_err(|err| self.interpreter.trace(err));
+        self.interpreter.truncate_calls(call_depth);
+        result
+    }
+
+    fn execute(&mut self, chunk: Rc<Chunk>, arguments: Vec<Value>) -> Result<Value, IntError> {
+        self.stack = arguments;
+        let mut frame = Frame {
+            chunk,
+            ip: 0,
+            base: 0,
+        };
+        loop {
+            self.interpreter.budget.step()?;
+            let op = frame.chunk.code[frame.ip];
+            frame.ip += 1;
+            match op {
+                Op::Constant(index) => {
+                    let value = frame.chunk.constants[index as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::Nil => self.stack.push(Value::Nil),
+                Op::True => self.stack.push(Value::Bool(true)),
+                Op::False => self.stack.push(Value::Bool(false)),
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::PopN(count) => {
+                    let len = self.stack.len() - count as usize;
+                    self.stack.truncate(len);
+                }
+                Op::Dup => self.stack.push(self.peek().clone()),
+                Op::Dup2 => {
+                    let len = self.stack.len();
+                    self.stack.extend_from_within(len - 2..);
+                }
+                Op::GetLocal(slot) => {
+                    let value = self.stack[frame.base + slot as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::SetLocal(slot) => {
+                    let value = self.peek().clone();
+                    self.stack[frame.base + slot as usize] = value;
+                }
+                Op::GetGlobal(name) => {
+                    let Some(value) = self.interpreter.globals.get(&name) else {
+                        return Err(self.interpreter.undefined_variable(frame.token()));
+                    };
+                    self.stack.push(value.clone());
+                }
+                Op::SetGlobal(name) => {
+                    let value = self.peek().clone();
+                    let Some(old_value) = self.interpreter.globals.get_mut(&name) else {
+                        return Err(self.interpreter.undefined_variable(frame.token()));
+                    };
+                    *old_value = value;
+                }
+                Op::DefineGlobal(name) => {
+                    let value = self.pop();
+                    self.interpreter.globals.insert(name, value);
+                }
+                Op::Unary => {
+                    let right = self.pop();
+                    self.stack.push(unary(frame.token(), right)?);
+                }
+                Op::Binary => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(binary(frame.token(), left, right)?);
+                }
+                Op::Jump(target) => frame.ip = target as usize,
+                Op::JumpIfFalse(target) => {
+                    if !self.peek().is_truthy() {
+                        frame.ip = target as usize;
+                    }
+                }
+                Op::Call(count) | Op::TailCall(count) => {
+                    let count = count as usize;
+                    let callee = self.stack.len() - count - 1;
+                    let fun = self.stack[callee]
+                        .clone()
+                        .get_fun()
+                        .with_token(frame.token())?;
+                    if fun.0.arity() != count {
+                        return Err(arity_error(&fun, count, frame.token()));
+                    }
+                    match fun.0.chunk() {
+                        Some(chunk) if matches!(op, Op::TailCall(_)) => {
+                            // the arguments take the place of the returning function's locals
+                            self.stack.drain(frame.base..=callee);
+                            self.interpreter.replace_frame(&fun);
+                            frame.chunk = chunk.clone();
+                            frame.ip = 0;
+                        }
+                        Some(chunk) => {
+                            self.interpreter.enter_call(&fun, frame.token())?;
+                            let callee_frame = Frame {
+                                chunk: chunk.clone(),
+                                ip: 0,
+                                base: callee + 1,
+                            };
+                            self.frames.push(mem::replace(&mut frame, callee_frame));
+                        }
+                        None => {
+                            let arguments = self.stack.split_off(callee + 1);
+                            self.pop();
+                            self.interpreter.enter_call(&fun, frame.token())?;
+                            let value = fun
+                                .0
+                                .call(self.interpreter, arguments)
+                                .map_err(|err| self.interpreter.trace(err));
+                            self.interpreter.exit_call();
+                            self.stack.push(value?);
+                        }
+                    }
+                }
+                Op::Return => {
+                    let value = self.pop();
+                    self.stack.truncate(frame.base);
+                    let Some(caller) = self.frames.pop() else {
+                        return Ok(value);
+                    };
+                    // the callee
+                    self.pop();
+                    self.stack.push(value);
+                    self.interpreter.exit_call();
+                    frame = caller;
+                }
+                Op::Print => println!("{}", self.pop()),
+                Op::Struct => self.stack.push(Value::new_struct(StructMap::default())),
+                Op::Field(name) => {
+                    let value = self.pop();
+                    let map = self.peek().get_struct().with_token(frame.token())?;
+                    if map.borrow_mut().insert(name, value).is_none() {
+                        memory::grow(FIELD_SIZE).with_token(frame.token())?;
+                    }
+                }
+                Op::Array(count) => {
+                    let elements = self.stack.split_off(self.stack.len() - count as usize);
+                    self.stack.push(Value::new_array(elements));
+                }
+                Op::StructGet(name) => {
+                    let target = self.pop();
+                    let map = target.get_struct().with_token(frame.token())?.borrow();
+                    let value = match map.get(name) {
+                        Some(value) => value.clone(),
+                        None if self.interpreter.strict_fields() => {
+                            return Err(missing_field(&map, frame.token()));
+                        }
+                        None => Value::Nil,
+                    };
+                    drop(map);
+                    self.stack.push(value);
+                }
+                Op::StructSet(name) => {
+                    let value = self.pop();
+                    let target = self.pop();
+                    let map = target.get_struct().with_token(frame.token())?;
+                    if map.borrow_mut().insert(name, value.clone()).is_none() {
+                        memory::grow(FIELD_SIZE).with_token(frame.token())?;
+                    }
+                    self.stack.push(value);
+                }
+                Op::IndexGet => {
+                    let index = self.pop();
+                    let array = self.pop();
+                    self.stack.push(index_get(&array, &index, frame.token())?);
+                }
+                Op::IndexSet(place) => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let array = self.pop();
+                    let string = index_set(array, index, value.clone(), frame.token())?;
+                    self.write_back(place, string, &frame)?;
+                    self.stack.push(value);
+                }
+                Op::AddAssign(place) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let variable = self.variable(place, &frame)?;
+                    let value = add_to_variable(variable, left, right, frame.token())?;
+                    self.stack.push(value);
+                }
+                Op::Append(place) => {
+                    let array = self.pop();
+                    // the appended value was evaluated first, it's below the place
+                    let below = match place {
+                        Place::Temporary | Place::Local(_) | Place::Global(_) => 1,
+                        Place::Field(_) => 2,
+                        Place::Index => 3,
+                    };
+                    let expression = self.stack.remove(self.stack.len() - below);
+                    if let Place::Local(_) | Place::Global(_) = place {
+                        let variable = self.variable(place, &frame)?;
+                        append_to_variable(variable, array, expression, frame.token())?;
+                    } else {
+                        let string = append(array, expression, frame.token())?;
+                        self.write_back(place, string, &frame)?;
+                    }
+                }
+                Op::Insert => {
+                    let index = self.pop();
+                    let expression = self.pop();
+                    let array = self.pop();
+                    insert(&array, index, expression, frame.token())?;
+                }
+                Op::Delete => {
+                    let index = self.pop();
+                    let array = self.pop();
+                    delete(&array, index, frame.token())?;
+                }
+            }
+        }
+    }
+
+    // the local or global `place` refers to
+    fn variable(&mut self, place: Place, frame: &Frame) -> Result<&mut Value, IntError> {
+        match place {
+            Place::Local(slot) => Ok(&mut self.stack[frame.base + slot as usize]),
+            Place::Global(name) => {
+                if !self.interpreter.globals.contains_key(&name) {
+                    return Err(self.interpreter.undefined_variable(frame.token()));
+                }
+                Ok(self
+                    .interpreter
+                    .globals
+                    .get_mut(&name)
+                    .expect("checked above"))
+            }
+            _ => unreachable!("only variables are written to in place"),
+        }
+    }
+
+    // stores a modified string where it was read from and pops what the place kept on the stack
+    fn write_back(
+        &mut self,
+        place: Place,
+        string: Option<Value>,
+        frame: &Frame,
+    ) -> Result<(), IntError> {
+        match place {
+            Place::Temporary => match string {
+                Some(_) => Err(unwritable_string(frame.token())),
+                None => Ok(()),
+            },
+            Place::Local(slot) => {
+                if let Some(string) = string {
+                    self.stack[frame.base + slot as usize] = string;
+                }
+                Ok(())
+            }
+            Place::Global(name) => {
+                let Some(string) = string else {
+                    return Ok(());
+                };
+                match self.interpreter.globals.get_mut(&name) {
+                    Some(value) => {
+                        *value = string;
+                        Ok(())
+                    }
+                    None => Err(self.interpreter.undefined_variable(frame.token())),
+                }
+            }
+            Place::Field(name) => {
+                let target = self.pop();
+                if let Some(string) = string {
+                    let map = target.get_struct().with_token(frame.token())?;
+                    map.borrow_mut().insert(name, string);
+                }
+                Ok(())
+            }
+            Place::Index => {
+                let index = self.pop();
+                let array = self.pop();
+                match string {
+                    Some(string) => match index_set(array, index, string, frame.token())? {
+                        // only one level of places is tracked, like in the tree walker
+                        Some(_) => Err(unwritable_string(frame.token())),
+                        None => Ok(()),
+                    },
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack
+            .pop()
+            .expect("the compiler keeps the stack balanced")
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack
+            .last()
+            .expect("the compiler keeps the stack balanced")
+    }
+}
@@ -0,0 +1,41 @@
+use std::{sync::atomic::Ordering, thread, time::Duration};
+
+use int::{
+    budget::Limit,
+    interpreter::{Engine, Interpreter, Outcome},
+};
+
+const CATCH_INTERRUPT: &str = "
+fun forever() { while (true) {} }
+var result = try(forever);
+if (result.stopped) exit(3);
+";
+
+#[test]
+fn try_catches_an_interrupt() {
+    for engine in [Engine::TreeWalker, Engine::Vm] {
+        let mut interpreter = Interpreter::with_engine(engine);
+        let interrupt = interpreter.interrupt_handle();
+        let interrupter = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            interrupt.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(
+            interpreter.interpret(CATCH_INTERRUPT.into()),
+            Outcome::Exit(3)
+        );
+        interrupter.join().unwrap();
+    }
+}
+
+#[test]
+fn a_caught_step_limit_still_stops_the_program() {
+    for engine in [Engine::TreeWalker, Engine::Vm] {
+        let mut interpreter = Interpreter::with_engine(engine);
+        interpreter.set_max_steps(Some(1000));
+        let outcome = interpreter.interpret(
+            "fun forever() { while (true) {} }\nvar result = try(forever);\nexit(3);\n".into(),
+        );
+        assert_eq!(outcome, Outcome::Stopped(Limit::Steps(1000)));
+    }
+}
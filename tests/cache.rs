@@ -0,0 +1,164 @@
+mod common;
+
+use std::fs;
+
+use common::{cache_home, int, script, stderr, stdout};
+use int::{
+    ast::Program,
+    cache,
+    expression::Expr,
+    interpreter::{Engine, Interpreter},
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    statement::Stmt,
+    token::TokenKind,
+};
+
+const SAMPLES: [&str; 7] = [
+    "array.int",
+    "literals.int",
+    "strings.int",
+    "struct.int",
+    "tail.int",
+    "cycles.int",
+    "unicode.int",
+];
+
+fn parsed(source: &str) -> Program {
+    let mut scanner = Scanner::new(source.into());
+    scanner.scan();
+    let mut parser = Parser::new(scanner);
+    let statements = parser.parse().expect("the sample parses");
+    Program {
+        ast: parser.ast,
+        statements,
+        ..Program::default()
+    }
+}
+
+fn program(source: &str) -> Program {
+    let mut program = parsed(source);
+    assert!(Resolver::default().resolve(&mut program).is_empty());
+    program
+}
+
+#[test]
+fn decoding_gives_back_the_encoded_program() {
+    for sample in SAMPLES {
+        let source = fs::read_to_string(sample).unwrap();
+        let encoded = cache::encode(&program(&source), &source, 0);
+        let decoded = cache::decode(&encoded, &source, 0).expect(sample);
+        assert_eq!(cache::encode(&decoded, &source, 0), encoded, "{sample}");
+    }
+}
+
+#[test]
+fn a_cache_of_other_source_is_ignored() {
+    let source = "var a = 1;\nprint a;\n";
+    let encoded = cache::encode(&program(source), source, 0);
+    assert!(cache::decode(&encoded, "var a = 2;\nprint a;\n", 0).is_none());
+}
+
+#[test]
+fn a_damaged_cache_is_ignored() {
+    let source = fs::read_to_string("struct.int").unwrap();
+    let encoded = cache::encode(&program(&source), &source, 0);
+    for length in 0..encoded.len() {
+        assert!(cache::decode(&encoded[..length], &source, 0).is_none());
+    }
+    // flipped bytes may still decode to some program, it only must not panic when it runs
+    let path = script("damaged", &source);
+    let cache = path.with_extension(cache::EXTENSION);
+    for index in 0..encoded.len() {
+        let mut damaged = encoded.clone();
+        damaged[index] ^= 0xff;
+        if cache::decode(&damaged, &source, 0).is_some() {
+            fs::write(&cache, &damaged).unwrap();
+            for engine in [Engine::TreeWalker, Engine::Vm] {
+                let mut interpreter = Interpreter::with_engine(engine);
+                interpreter.set_max_steps(Some(100_000));
+                interpreter.interpret_cached(source.clone(), &cache);
+            }
+        }
+    }
+}
+
+#[test]
+fn a_tree_that_contains_itself_is_ignored() {
+    let source = "{ print 1; }\n";
+    let mut program = program(source);
+    let block = program.statements[0];
+    program.ast[block] = Stmt::Block {
+        statements: vec![block],
+    };
+    assert!(cache::decode(&cache::encode(&program, source, 0), source, 0).is_none());
+}
+
+#[test]
+fn an_operator_the_engines_dont_know_is_ignored() {
+    let source = "print 1 + 2;\n";
+    let mut program = program(source);
+    let Stmt::Print { expression } = program.ast[program.statements[0]] else {
+        panic!("the program is a print statement");
+    };
+    let Expr::Binary { operator, .. } = &mut program.ast[expression] else {
+        panic!("it prints a sum");
+    };
+    operator.kind = TokenKind::Semicolon;
+    assert!(cache::decode(&cache::encode(&program, source, 0), source, 0).is_none());
+}
+
+#[test]
+fn a_program_that_doesnt_resolve_is_ignored() {
+    let source = "break;\n";
+    let program = parsed(source);
+    assert!(cache::decode(&cache::encode(&program, source, 0), source, 0).is_none());
+}
+
+#[test]
+fn runs_write_the_cache_and_start_from_it() {
+    let path = script("cached", "var a = [1, 2];\nprint a;\nprint \"x\\ty\";\n");
+    let cache_dir = cache_home(&path).join("int");
+    _ = fs::remove_dir_all(&cache_dir);
+    let first = int(&[], &path);
+    let caches: Vec<_> = fs::read_dir(&cache_dir).unwrap().collect();
+    assert_eq!(caches.len(), 1);
+    let cache = caches[0].as_ref().unwrap().path();
+    let bytes = fs::read(&cache).unwrap();
+
+    let second = int(&[], &path);
+    assert_eq!(stdout(&second), stdout(&first));
+
+    fs::write(&cache, &bytes[..bytes.len() / 2]).unwrap();
+    let damaged = int(&[], &path);
+    assert_eq!(stdout(&damaged), stdout(&first));
+    assert_eq!(
+        fs::read(&cache).unwrap(),
+        bytes,
+        "the damaged cache is written again"
+    );
+}
+
+#[test]
+fn a_cache_that_cant_be_written_is_a_warning() {
+    let path = script("unwritable", "print 1;\n");
+    // a file where the cache directory should be
+    fs::create_dir_all(cache_home(&path)).unwrap();
+    fs::write(cache_home(&path).join("int"), "").unwrap();
+    let output = int(&[], &path);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "1\n");
+    assert!(stderr(&output).contains("WARNING: Couldn't write the cache"));
+}
+
+#[test]
+fn a_run_from_the_cache_gives_the_same_warnings() {
+    let path = script("warnings", "print \"a\\db\";\n");
+    _ = fs::remove_dir_all(cache_home(&path).join("int"));
+    let first = int(&[], &path);
+    assert!(stderr(&first).contains("W0008"), "{}", stderr(&first));
+    let second = int(&[], &path);
+    assert_eq!(stderr(&second), stderr(&first));
+    assert_eq!(second.status.code(), first.status.code());
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::{int, script, stderr, stdout};
+
+#[test]
+fn resolver_errors_go_to_stderr() {
+    let path = script(
+        "resolver",
+        "fun f() { break; }\nprint 1;\n{ var a = a; }\nreturn;\n",
+    );
+    let output = int(&[], &path);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stdout(&output), "");
+    let stderr = stderr(&output);
+    for message in [
+        "break is only allowed in loops.",
+        "Can't read a local variable in its own initializer.",
+        "Top level return is not allowed.",
+    ] {
+        assert!(stderr.contains(message), "{message} in {stderr}");
+    }
+}
+
+#[test]
+fn field_names_made_at_runtime_count_against_the_memory_limit() {
+    let path = script(
+        "keys",
+        "var m = {};\nvar i = 0;\nwhile (i < 100000) { m[str(i)] = i; i = i + 1; }\n",
+    );
+    for engine in ["--engine=tree", "--engine=vm"] {
+        let output = int(&[engine, "--max-memory=3000000"], &path);
+        assert_eq!(output.status.code(), Some(70), "{engine}");
+        assert!(stderr(&output).contains("memory limit of 3000000 bytes exceeded"));
+    }
+}
+
+#[test]
+fn a_stopped_program_has_its_own_exit_status() {
+    let path = script("stopped", "while (true) {}\n");
+    for engine in ["--engine=tree", "--engine=vm"] {
+        let output = int(&[engine, "--max-steps=1000"], &path);
+        assert_eq!(output.status.code(), Some(75), "{engine}");
+        assert!(stderr(&output).contains("Execution stopped: step limit of 1000 exceeded"));
+    }
+}
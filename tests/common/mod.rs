@@ -0,0 +1,41 @@
+// every test binary uses some of these
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// Writes `source` to a script named `name` in a fresh directory for the test.
+pub fn script(name: &str, source: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("int-test-{}-{name}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join(format!("{name}.int"));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+pub fn int(args: &[&str], script: &Path) -> Output {
+    // run next to the script so that errors name it the same way on every machine
+    Command::new(env!("CARGO_BIN_EXE_int"))
+        .current_dir(script.parent().unwrap())
+        .args(args)
+        .arg(script.file_name().unwrap())
+        .env("XDG_CACHE_HOME", cache_home(script))
+        .output()
+        .unwrap()
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// The cache home of the runs of `script`, caches go to its `int` directory.
+pub fn cache_home(script: &Path) -> PathBuf {
+    script.parent().unwrap().join("cache")
+}
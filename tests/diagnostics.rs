@@ -0,0 +1,134 @@
+mod common;
+
+use common::{int, script, stderr};
+
+/// Runs `source` on both engines with JSON errors and checks that each prints `expected` and
+/// exits with `status`.
+fn assert_json(name: &str, source: &str, status: i32, expected: &str) {
+    let path = script(name, source);
+    for engine in ["--engine=tree", "--engine=vm"] {
+        let output = int(
+            &[
+                engine,
+                "--no-cache",
+                "--error-format=json",
+                "--strict-fields",
+                "--max-depth=20",
+                "--max-memory=100000",
+            ],
+            &path,
+        );
+        assert_eq!(stderr(&output), format!("{expected}\n"), "{name} {engine}");
+        assert_eq!(output.status.code(), Some(status), "{name} {engine}");
+    }
+}
+
+#[test]
+fn syntax() {
+    assert_json(
+        "syntax",
+        "print a != not b;\n",
+        65,
+        r#"{"severity":"error","code":"E0100","message":"`not` only goes after `is`, use `!` to negate a value","file":"syntax.int","span":{"start":11,"end":14},"line":1,"column":12,"labels":[],"notes":[]}"#,
+    );
+}
+
+#[test]
+fn expected_token() {
+    assert_json(
+        "expected_token",
+        "print (1;\n",
+        65,
+        r#"{"severity":"error","code":"E0101","message":"Unmatched delimiter: Expected `)`","file":"expected_token.int","span":{"start":8,"end":9},"line":1,"column":9,"labels":[{"message":"unclosed delimiter","span":{"start":6,"end":7},"line":1,"column":7}],"notes":[]}"#,
+    );
+}
+
+#[test]
+fn expected_expression() {
+    assert_json(
+        "expected_expression",
+        "print ;\n",
+        65,
+        r#"{"severity":"error","code":"E0102","message":"Expected Expression","file":"expected_expression.int","span":{"start":6,"end":7},"line":1,"column":7,"labels":[],"notes":[]}"#,
+    );
+}
+
+#[test]
+fn invalid_target() {
+    assert_json(
+        "invalid_target",
+        "1 = 2;\n",
+        65,
+        r#"{"severity":"error","code":"E0103","message":"Invalid assignment target","file":"invalid_target.int","span":{"start":2,"end":3},"line":1,"column":3,"labels":[],"notes":[]}"#,
+    );
+}
+
+#[test]
+fn undefined_variable() {
+    assert_json(
+        "undefined_variable",
+        "var count = 1;\nprint cuont;\n",
+        70,
+        r#"{"severity":"error","code":"E0302","message":"Undefined variable `cuont`. Did you mean `count`?","file":"undefined_variable.int","span":{"start":21,"end":26},"line":2,"column":7,"labels":[],"notes":[]}"#,
+    );
+}
+
+#[test]
+fn type_mismatch() {
+    assert_json(
+        "type_mismatch",
+        "print -\"a\";\n",
+        70,
+        r#"{"severity":"error","code":"E0303","message":"Operand must be a number","file":"type_mismatch.int","span":{"start":6,"end":7},"line":1,"column":7,"labels":[],"notes":[]}"#,
+    );
+}
+
+#[test]
+fn index_out_of_bounds() {
+    assert_json(
+        "index_out_of_bounds",
+        "var a = [1];\nprint a[3];\n",
+        70,
+        r#"{"severity":"error","code":"E0304","message":"index `3` is out of bound `1`","file":"index_out_of_bounds.int","span":{"start":22,"end":23},"line":2,"column":10,"labels":[],"notes":[]}"#,
+    );
+}
+
+#[test]
+fn arity() {
+    assert_json(
+        "arity",
+        "fun f(a) {}\nf();\n",
+        70,
+        r#"{"severity":"error","code":"E0305","message":"Expected 1 arguments, got 0","file":"arity.int","span":{"start":14,"end":15},"line":2,"column":3,"labels":[{"message":"function defined here","span":{"start":4,"end":5},"line":1,"column":5}],"notes":[]}"#,
+    );
+}
+
+#[test]
+fn recursion_depth() {
+    assert_json(
+        "recursion_depth",
+        "fun f() { return 1 + f(); }\nf();\n",
+        70,
+        r#"{"severity":"error","code":"E0306","message":"maximum recursion depth exceeded","file":"recursion_depth.int","span":{"start":23,"end":24},"line":1,"column":24,"labels":[],"notes":["in f at line 1","called from f at line 1","called from f at line 1","... repeated 17 more times","called from <script> at line 2"]}"#,
+    );
+}
+
+#[test]
+fn memory_limit() {
+    assert_json(
+        "memory_limit",
+        "var a = [];\nwhile (true) append(a, 1);\n",
+        70,
+        r#"{"severity":"error","code":"E0307","message":"memory limit of 100000 bytes exceeded","file":"memory_limit.int","span":{"start":31,"end":32},"line":2,"column":20,"labels":[],"notes":[]}"#,
+    );
+}
+
+#[test]
+fn missing_field() {
+    assert_json(
+        "missing_field",
+        "var s = {width: 1};\nprint s.widht;\n",
+        70,
+        r#"{"severity":"error","code":"E0308","message":"Struct has no field `widht`. Did you mean `width`?","file":"missing_field.int","span":{"start":28,"end":33},"line":2,"column":9,"labels":[],"notes":[]}"#,
+    );
+}
@@ -0,0 +1,57 @@
+use std::process::{Command, Output};
+
+// the scripts in the repository that finish on their own and print the same thing every run,
+// some read files relative to the repository
+const SAMPLES: [&str; 14] = [
+    "array.int",
+    "cycles.int",
+    "deep_clone.int",
+    "gc.int",
+    "identity.int",
+    "index.int",
+    "literals.int",
+    "main.int",
+    "str.int",
+    "strings.int",
+    "struct.int",
+    "tail.int",
+    "unicode.int",
+    "aoc/day1.int",
+];
+
+fn run(sample: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_int"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .arg("--no-cache")
+        .arg(sample)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn engines_print_the_same() {
+    for sample in SAMPLES {
+        let expected = run(sample, &["--engine=tree"]);
+        assert!(expected.status.success(), "{sample}");
+        assert!(!expected.stdout.is_empty(), "{sample}");
+        for args in [&["--engine=vm"][..], &["-O"], &["--engine=vm", "-O"]] {
+            let output = run(sample, args);
+            assert_eq!(
+                output.status.code(),
+                expected.status.code(),
+                "{sample} {args:?}"
+            );
+            assert_eq!(
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&expected.stdout),
+                "{sample} {args:?}"
+            );
+            assert_eq!(
+                String::from_utf8_lossy(&output.stderr),
+                String::from_utf8_lossy(&expected.stderr),
+                "{sample} {args:?}"
+            );
+        }
+    }
+}
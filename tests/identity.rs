@@ -0,0 +1,28 @@
+use int::interpreter::{Engine, Interpreter, Outcome};
+
+const STRINGS: &str = r#"
+var s = "ab";
+var t = s;
+t[0] = "x";
+if (s is not "a" + "b") exit(1);
+if (s is t) exit(2);
+if (t is not "xb") exit(3);
+var b = builder("ab");
+if (b is builder("ab")) exit(4);
+if (b is not b) exit(5);
+"#;
+
+#[test]
+fn strings_are_identical_when_they_are_equal() {
+    for engine in [Engine::TreeWalker, Engine::Vm] {
+        for optimize in [false, true] {
+            let mut interpreter = Interpreter::with_engine(engine);
+            interpreter.set_optimize(optimize);
+            assert_eq!(
+                interpreter.interpret(STRINGS.into()),
+                Outcome::Success,
+                "{engine:?} {optimize}"
+            );
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use ahash::AHashMap as HashMap;
+use int::{
+    ast::Program,
+    lint::{Lint, Linter, Lints},
+    parser::Parser,
+    resolver::Resolver,
+    scanner::Scanner,
+    symbol::Symbol,
+};
+
+/// The code and the message of every warning in `source`, with `len` as the only native.
+fn lint(source: &str, lints: &Lints) -> Vec<(&'static str, String)> {
+    let mut scanner = Scanner::new(source.into());
+    scanner.scan();
+    let mut parser = Parser::new(scanner);
+    let statements = parser.parse().expect("the source parses");
+    let mut program = Program {
+        ast: parser.ast,
+        statements,
+        ..Program::default()
+    };
+    assert!(Resolver::default().resolve(&mut program).is_empty());
+    let natives = HashMap::from_iter([(Symbol::intern("len"), 1)]);
+    Linter::new(&program.ast, lints, natives)
+        .lint(&program)
+        .into_iter()
+        .map(|warning| (warning.code.as_str(), warning.message))
+        .collect()
+}
+
+fn warnings(source: &str) -> Vec<(&'static str, String)> {
+    lint(source, &Lints::default())
+}
+
+#[test]
+fn unused_variables_and_parameters() {
+    assert_eq!(
+        warnings("fun f(a, b) { var c = 1; var _d = 2; return a; }\nf(1, 2);\n"),
+        [
+            ("W0002", "Unused parameter `b`.".into()),
+            ("W0001", "Unused variable `c`.".into()),
+        ]
+    );
+}
+
+#[test]
+fn redeclaration() {
+    assert_eq!(
+        warnings("var a = 1;\nvar a = 2;\nprint a;\n"),
+        [("W0003", "`a` is already declared in this scope.".into())]
+    );
+}
+
+#[test]
+fn shadowed_native() {
+    assert_eq!(
+        warnings("fun len(x) { return x; }\nprint len(1);\n"),
+        [("W0004", "`len` shadows the native function `len`.".into())]
+    );
+}
+
+#[test]
+fn unreachable() {
+    assert_eq!(
+        warnings("fun f() {\n    return 1;\n    print 2;\n}\nf();\n"),
+        [("W0005", "Code after this is unreachable.".into())]
+    );
+}
+
+#[test]
+fn undeclared_assignment() {
+    assert_eq!(
+        warnings("totl = 5;\nvar total = 0;\nprint total;\n"),
+        [(
+            "W0006",
+            "Assignment to undeclared global `totl`. Did you mean `total`?".into()
+        )]
+    );
+}
+
+#[test]
+fn argument_count() {
+    assert_eq!(
+        warnings("fun add(a, b) { return a + b; }\nprint add(1, 2, 3);\nprint len(1, 2);\n"),
+        [
+            ("W0007", "`add` expects 2 arguments, got 3.".into()),
+            ("W0007", "`len` expects 1 argument, got 2.".into()),
+        ]
+    );
+}
+
+#[test]
+fn allowed_lints_are_not_reported() {
+    let source = "var a = 1;\nvar a = 2;\n{ var b = 1; }\n";
+    let mut lints = Lints::default();
+    lints.allow(Lint::Redeclaration);
+    assert_eq!(
+        lint(source, &lints),
+        [("W0001", "Unused variable `b`.".into())]
+    );
+    lints.allow(Lint::UnusedVariable);
+    assert!(lint(source, &lints).is_empty());
+}
+
+#[test]
+fn lint_names() {
+    for lint in Lint::ALL {
+        assert_eq!(Lint::from_name(lint.name()), Some(lint));
+    }
+    assert_eq!(Lint::from_name("unused"), None);
+}
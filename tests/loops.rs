@@ -0,0 +1,35 @@
+use std::{fs, process::Command};
+
+/// Runs `source` as a script named `name` and returns what it printed.
+fn run(name: &str, source: &str) -> String {
+    let directory = std::env::temp_dir().join(format!("int-test-{}-{name}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join(format!("{name}.int"));
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_int"))
+        .arg(&path)
+        .env("XDG_CACHE_HOME", directory.join("cache"))
+        .output()
+        .unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn continue_goes_to_the_next_iteration() {
+    let while_loop = "
+var i = 0;
+while (i < 5) {
+    i = i + 1;
+    if (i == 2) continue;
+    print i;
+}
+";
+    assert_eq!(run("while_continue", while_loop), "1\n3\n4\n5\n");
+    let for_loop = "
+for (var i = 0; i < 3; i = i + 1) {
+    if (i == 1) continue;
+    print i;
+}
+";
+    assert_eq!(run("for_continue", for_loop), "0\n2\n");
+}
@@ -0,0 +1,72 @@
+use int::interpreter::{Engine, Interpreter, Outcome};
+
+#[test]
+fn objects_are_tracked() {
+    for limit in [None, Some(1 << 20)] {
+        for engine in [Engine::TreeWalker, Engine::Vm] {
+            let mut interpreter = Interpreter::with_engine(engine);
+            interpreter.set_memory_limit(limit);
+            let outcome = interpreter.interpret("var a = [1, 2, 3];\nvar s = {x: 1};\n".into());
+            assert_eq!(outcome, Outcome::Success);
+            let stats = interpreter.memory_stats();
+            assert!(
+                stats.arrays >= 1 && stats.structs >= 1,
+                "{limit:?} {stats:?}"
+            );
+            assert!(stats.bytes() > 0, "{limit:?} {stats:?}");
+        }
+    }
+}
+
+#[test]
+fn the_limit_only_applies_to_its_interpreter() {
+    let source = "var a = [];\nvar i = 0;\nwhile (i < 10000) { append(a, i); i = i + 1; }\n";
+    let mut limited = Interpreter::default();
+    limited.set_memory_limit(Some(10_000));
+    assert_eq!(limited.interpret(source.into()), Outcome::RuntimeError);
+    assert_eq!(
+        Interpreter::default().interpret(source.into()),
+        Outcome::Success
+    );
+}
+
+#[test]
+fn overwriting_a_field_takes_no_memory() {
+    for engine in [Engine::TreeWalker, Engine::Vm] {
+        let mut interpreter = Interpreter::with_engine(engine);
+        interpreter.set_memory_limit(Some(1 << 20));
+        interpreter.interpret("var s = {x: 0};\ns[\"y\"] = 0;\n".into());
+        let before = interpreter.memory_stats().struct_bytes;
+        let outcome = interpreter.interpret(
+            "var i = 0;\nwhile (i < 1000) { s.x = i; s[\"y\"] = i; i = i + 1; }\n".into(),
+        );
+        assert_eq!(outcome, Outcome::Success);
+        assert_eq!(interpreter.memory_stats().struct_bytes, before);
+    }
+}
+
+#[test]
+fn field_names_made_at_runtime_are_freed_with_their_struct() {
+    for engine in [Engine::TreeWalker, Engine::Vm] {
+        let mut interpreter = Interpreter::with_engine(engine);
+        interpreter.set_memory_limit(Some(1 << 20));
+        let outcome = interpreter.interpret(
+            "var i = 0;\nwhile (i < 100000) { var m = {}; m[\"key\" + str(i)] = i; i = i + 1; }\n"
+                .into(),
+        );
+        assert_eq!(outcome, Outcome::Success);
+    }
+}
+
+#[test]
+fn a_field_name_interned_later_finds_its_field() {
+    let mut interpreter = Interpreter::default();
+    interpreter.set_strict_fields(true);
+    let outcome = interpreter.interpret("var m = {};\nm[\"late\" + \"r\"] = 1;\n".into());
+    assert_eq!(outcome, Outcome::Success);
+    let outcome = interpreter.interpret(
+        "m.later = m.later + 1;\nm[\"later\"] = m[\"later\"] + 1;\nif (m.later != 3 or len(keys(m)) != 1) exit(1);\n"
+            .into(),
+    );
+    assert_eq!(outcome, Outcome::Success);
+}
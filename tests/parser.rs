@@ -0,0 +1,73 @@
+use int::{diagnostic::Diagnostic, parser::Parser, scanner::Scanner};
+
+fn parse(source: &str) -> Result<(), Vec<Diagnostic>> {
+    let mut scanner = Scanner::new(source.into());
+    scanner.scan();
+    Parser::new(scanner).parse().map(|_| ())
+}
+
+fn messages(source: &str) -> Vec<String> {
+    parse(source)
+        .expect_err("the source has errors")
+        .into_iter()
+        .map(|diagnostic| diagnostic.message)
+        .collect()
+}
+
+#[test]
+fn is_and_is_not() {
+    assert!(parse("print a is b;").is_ok());
+    assert!(parse("print a is not b;").is_ok());
+    assert!(parse("print a == b != c;").is_ok());
+}
+
+#[test]
+fn not_only_follows_is() {
+    for source in ["print a != not b;", "print a == not b;"] {
+        assert_eq!(
+            messages(source),
+            ["`not` only goes after `is`, use `!` to negate a value"],
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn every_statement_with_an_error_is_reported() {
+    assert_eq!(messages("var = 1;\nprint (;\nprint 1;\n").len(), 2);
+}
+
+#[test]
+fn unclosed_delimiter_points_at_the_opening_one() {
+    let diagnostics = parse("print [1, 2;").expect_err("the array isn't closed");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].labels.len(), 1);
+    assert_eq!(diagnostics[0].labels[0].message, "unclosed delimiter");
+    assert_eq!(diagnostics[0].labels[0].span.start, 6);
+}
+
+#[test]
+fn scanner_errors_are_reported_once() {
+    assert_eq!(messages("print \"open;\n").len(), 1);
+}
+
+#[test]
+fn unknown_escapes_are_kept_with_a_warning() {
+    let mut scanner = Scanner::new(r#"print "a\db\n";"#.into());
+    scanner.scan();
+    let mut parser = Parser::new(scanner);
+    assert!(parser.parse().is_ok());
+    let warnings: Vec<&str> = parser
+        .warnings
+        .iter()
+        .map(|warning| warning.message.as_str())
+        .collect();
+    assert_eq!(
+        warnings,
+        ["Unknown escape sequence `\\d`, it's kept as it is."]
+    );
+    assert_eq!(
+        int::scanner::string_literal_value(r#""a\db\n""#).0,
+        "a\\db\n"
+    );
+}
@@ -0,0 +1,23 @@
+use std::thread;
+
+use int::interpreter::{Engine, Interpreter, Outcome};
+
+// the size of a main thread's stack
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+#[test]
+fn the_default_depth_fits_on_a_main_threads_stack() {
+    for engine in [Engine::TreeWalker, Engine::Vm] {
+        let outcome = thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                Interpreter::with_engine(engine).interpret(
+                    "fun f(n) { var a = [n]; return 1 + f(n + 1); }\nprint f(0);\n".into(),
+                )
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(outcome, Outcome::RuntimeError);
+    }
+}
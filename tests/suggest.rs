@@ -0,0 +1,35 @@
+use int::suggest::{closest, did_you_mean, edit_distance};
+
+#[test]
+fn distances() {
+    assert_eq!(edit_distance("count", "count"), 0);
+    assert_eq!(edit_distance("count", "cuont"), 1);
+    assert_eq!(edit_distance("count", "coun"), 1);
+    assert_eq!(edit_distance("count", "mount"), 1);
+    assert_eq!(edit_distance("", "abc"), 3);
+}
+
+#[test]
+fn close_names_are_suggested_closest_first() {
+    let names = ["counter", "count", "amount", "total"];
+    assert_eq!(closest("cuont", names), ["count"]);
+    assert_eq!(closest("countr", names), ["count", "counter"]);
+    assert!(closest("total", names).is_empty());
+}
+
+#[test]
+fn short_names_get_no_suggestions() {
+    assert!(closest("y", ["f", "x"]).is_empty());
+    assert!(closest("ab", ["a", "b", "abc"]).is_empty());
+    assert_eq!(closest("abd", ["abc"]), ["abc"]);
+}
+
+#[test]
+fn suggestions_read_as_a_sentence() {
+    assert_eq!(did_you_mean(&[]), "");
+    assert_eq!(did_you_mean(&["a"]), " Did you mean `a`?");
+    assert_eq!(
+        did_you_mean(&["a", "b", "c"]),
+        " Did you mean `a`, `b` or `c`?"
+    );
+}